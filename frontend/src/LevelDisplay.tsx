@@ -28,6 +28,8 @@ function LevelDisplay({ level, size, description }: Args) {
                   tileType = "ground";
                 if (description.grills.find((v) => v[0] === x && v[1] === y))
                   tileType = "grill";
+                if (description.ladders?.find((v) => v[0] === x && v[1] === y))
+                  tileType = "ladder";
                 return (
                   <div key={x} className={`tile ${tileType}`}>
                     {level.sausages.map((s) => {
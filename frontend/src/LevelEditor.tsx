@@ -1,5 +1,11 @@
 import { useEffect, useState } from "react";
-import { IVec3, LevelDescription, LevelGraph, Sausage } from "./types";
+import {
+  IVec3,
+  LevelDescription,
+  LevelGraph,
+  Ruleset,
+  Sausage,
+} from "./types";
 import { produce } from "immer";
 import TileGrid from "./LevelEditor/TileGrid";
 import SizeControls from "./LevelEditor/SizeControls";
@@ -15,12 +21,14 @@ function LevelEditor(args: Args) {
   ]);
   const [groundTiles, setGroundTiles] = useState<Set<string>>(new Set());
   const [grillTiles, setGrillTiles] = useState<Set<string>>(new Set());
+  const [ladderTiles, setLadderTiles] = useState<Set<string>>(new Set());
 
   const [tool, setTool] = useState<Tool>("remove");
 
   const [sausages, setSausages] = useState<Sausage[]>([]);
   const [startDir, setStartDir] = useState<PlayerDirections>([0, -1, 0]);
   const [startPos, setStartPos] = useState<IVec3 | null>(null);
+  const [rules, setRules] = useState<Ruleset | undefined>(undefined);
 
   const levelDescription = (() => {
     let playerDirVector = startDir;
@@ -31,13 +39,19 @@ function LevelEditor(args: Args) {
     let grills: IVec3[] = [...grillTiles].map((t) => {
       return JSON.parse(t) as IVec3;
     });
+    let ladders: IVec3[] = [...ladderTiles].map((t) => {
+      return JSON.parse(t) as IVec3;
+    });
 
     return {
       start_pos: startPos ?? [-1, -1, -1],
       start_dir: playerDirVector,
       ground,
       grills,
+      ladders,
       sausages,
+      // leave the rules out rather than sending undefined when there are none
+      ...(rules && { rules }),
     };
   })();
 
@@ -55,6 +69,10 @@ function LevelEditor(args: Args) {
     setSausages(description.sausages);
     setGroundTiles(new Set(description.ground.map((t) => JSON.stringify(t))));
     setGrillTiles(new Set(description.grills.map((t) => JSON.stringify(t))));
+    setLadderTiles(
+      new Set((description.ladders ?? []).map((t) => JSON.stringify(t))),
+    );
+    setRules(description.rules);
   };
 
   const clearTile: (x: number, y: number, z: number) => void = (
@@ -95,6 +113,13 @@ function LevelEditor(args: Args) {
         }),
       );
     }
+    if (ladderTiles.has(JSON.stringify([x, y, z]))) {
+      setLadderTiles(
+        produce(ladderTiles, (s) => {
+          s.delete(JSON.stringify([x, y, z]));
+        }),
+      );
+    }
   };
 
   useEffect(() => {
@@ -195,6 +220,12 @@ function LevelEditor(args: Args) {
               );
             } else if (JSON.stringify(startPos) === JSON.stringify([x, y, z])) {
               setStartPos(null);
+            } else if (ladderTiles.has(JSON.stringify([x, y, z]))) {
+              setLadderTiles(
+                produce(ladderTiles, (s) => {
+                  s.delete(JSON.stringify([x, y, z]));
+                }),
+              );
             } else if (groundTiles.has(JSON.stringify([x, y, z]))) {
               setGroundTiles(
                 produce(groundTiles, (s) => {
@@ -230,6 +261,13 @@ function LevelEditor(args: Args) {
                 }),
               );
             }
+          } else if (tool === "ladder") {
+            clearTile(x, y, z);
+            setLadderTiles(
+              produce(ladderTiles, (s) => {
+                s.add(JSON.stringify([x, y, z]));
+              }),
+            );
           } else if (tool === "grill") {
             if (z !== 0) {
               clearTile(x, y, z - 1);
@@ -1,6 +1,6 @@
 import { IVec3 } from "../types";
 
-export type Tool = "remove" | "grass" | "dirt" | "grill";
+export type Tool = "remove" | "grass" | "dirt" | "grill" | "ladder";
 export type PlayerDirections = [0, -1, 0] | [1, 0, 0] | [0, 1, 0] | [-1, 0, 0];
 
 function Controls(args: {
@@ -35,6 +35,12 @@ function Controls(args: {
       >
         Grill
       </button>
+      <button
+        className={args.tool === "ladder" ? "active" : ""}
+        onClick={() => args.setTool("ladder")}
+      >
+        Ladder
+      </button>
       <button
         draggable="true"
         onDragStart={(e) => {
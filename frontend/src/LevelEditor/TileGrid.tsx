@@ -26,6 +26,9 @@ const TileGrid = (args: {
   const grillTiles = new Set(
     args.description.grills.map((v) => JSON.stringify(v)),
   );
+  const ladderTiles = new Set(
+    (args.description.ladders ?? []).map((v) => JSON.stringify(v)),
+  );
 
   const layersControls = [];
   for (let i = 0; i < args.lenZ; i++) {
@@ -85,6 +88,7 @@ const TileGrid = (args: {
       let tileType;
       if (groundTiles.has(JSON.stringify([x, y, z]))) tileType = "dirt";
       else if (grillTiles.has(JSON.stringify([x, y, z]))) tileType = "dirt";
+      else if (ladderTiles.has(JSON.stringify([x, y, z]))) tileType = "ladder";
       else if (groundTiles.has(JSON.stringify([x, y, z - 1])))
         tileType = "grass";
       else if (grillTiles.has(JSON.stringify([x, y, z - 1])))
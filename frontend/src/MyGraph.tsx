@@ -16,8 +16,21 @@ const colors = {
   selectedColor: new Three.Color().setHex(0xfff176),
   burntColor: new Three.Color().setHex(0xa80a01),
   lostColor: new Three.Color().setHex(0x013ea8),
+  drownedColor: new Three.Color().setHex(0x0a6c8f),
   originColor: new Three.Color().setHex(0x62af54),
   solvedColor: new Three.Color().setHex(0x1aa801),
+  doomedColor: new Three.Color().setHex(0x6b4a45),
+};
+
+const incompleteReasons: Record<
+  NonNullable<LevelGraph["incomplete"]>,
+  string
+> = {
+  state_limit: "it reached the limit on states",
+  edge_limit: "it reached the limit on edges",
+  depth_limit: "it reached the limit on moves",
+  cancelled: "it was cancelled",
+  unexplored: "the rest has not been explored yet",
 };
 
 const getColor: (state: LevelState) => Three.Color = (state: LevelState) => {
@@ -25,6 +38,8 @@ const getColor: (state: LevelState) => Three.Color = (state: LevelState) => {
   if (state.status === "Lost") return colors.lostColor;
   if (state.status === "Solution") return colors.solvedColor;
   if (state.status === "Burnt") return colors.burntColor;
+  if (state.status === "Drowned") return colors.drownedColor;
+  if (state.doomed) return colors.doomedColor;
   return colors.defaultColor;
 };
 
@@ -227,6 +242,12 @@ export const MyGraph = ({ solution }: Args) => {
   return (
     <>
       <div ref={container}></div>
+      {solution.incomplete !== null && (
+        <p>
+          Only part of the graph is shown, the search stopped early:{" "}
+          {incompleteReasons[solution.incomplete]}
+        </p>
+      )}
       <button
         type="button"
         defaultValue={""}
@@ -18,22 +18,18 @@ export interface Sausage {
 }
 
 export type SausageOrientation = "Vertical" | "Horizontal";
-export type TileType = "water" | "ground" | "grill";
-
-export interface NodeNeighbors {
-  forward: number;
-  back: number;
-  right: number;
-  left: number;
-}
+export type TileType = "water" | "ground" | "grill" | "ladder";
 
 export interface LevelState {
   id: number;
+  fingerprint: string;
   player_pos: IVec3;
   player_dir: IVec3;
   sausages: Sausage[];
-  neighbors: NodeNeighbors | null;
-  status: "Lost" | "Unsolved" | "Solution" | "Burnt";
+  carried: number | null;
+  status: "Lost" | "Unsolved" | "Solution" | "Burnt" | "Drowned";
+  distance: number | null;
+  doomed: boolean;
   is_initial?: boolean;
 }
 
@@ -62,11 +58,18 @@ export namespace LevelState {
   }
 }
 
+export type Move = "forward" | "back" | "left" | "right";
+
+export type Hint =
+  | { move: Move }
+  | "solved"
+  | { unwinnable: LevelState["status"] }
+  | "unknown";
+
 export interface Edge {
-  id: number;
   source: number;
   target: number;
-  movement: string;
+  movement: Move;
 }
 
 export interface LevelGraph {
@@ -74,11 +77,26 @@ export interface LevelGraph {
   edges: Edge[];
   initial_state: LevelState;
   level_description: LevelDescription;
+  incomplete:
+    | null
+    | "state_limit"
+    | "edge_limit"
+    | "depth_limit"
+    | "cancelled"
+    | "unexplored";
 }
 
 export namespace LevelGraph {
   export function fromDescription(data: LevelDescription): LevelGraph {
-    const solution: LevelGraph = JSON.parse(solve(data));
+    // the graph lives in wasm memory until it is freed, the UI only needs
+    // the plain copy
+    const graph = solve(data, { max_states: 50000 });
+    let solution: LevelGraph;
+    try {
+      solution = graph.to_js();
+    } finally {
+      graph.free();
+    }
     const initial_id = solution.initial_state.id;
     const initial_state = solution.states.find(
       (state) => state.id === initial_id,
@@ -96,7 +114,16 @@ export interface LevelDescription {
   start_dir: IVec3;
   ground: IVec3[];
   grills: IVec3[];
+  ladders?: IVec3[];
   sausages: Sausage[];
+  rules?: Ruleset;
+}
+
+export interface Ruleset {
+  return_to_start: boolean;
+  max_cooked: number;
+  grill_bounce: boolean;
+  sausages_fall: boolean;
 }
 
 export namespace LevelDescription {
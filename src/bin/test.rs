@@ -6,7 +6,10 @@ fn main() {
 
     let solution = solver::generate_graph(&serde_json::from_str(&description).unwrap());
     println!("Solution created");
+    println!(
+        "nodes generated: {}, {} bytes per node",
+        solution.state_count(),
+        solution.heap_size() / solution.state_count()
+    );
     graph::run(solution);
-
-    //println!("nodes generated: {}", solution.states.len())
 }
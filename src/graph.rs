@@ -1,6 +1,4 @@
 //! this will be important https://www.sccs.swarthmore.edu/users/10/mkelly1/quadtrees.pdf
-use std::sync::Arc;
-
 use avian3d::prelude::*;
 use bevy::{
     asset::RenderAssetUsages,
@@ -77,18 +75,24 @@ fn setup_nodes(
     let graph = &graph.into_inner().0;
     let mesh = mesh_assets.add(Mesh::from(Sphere::new(1.0)));
     let material = material_assets.add(StandardMaterial::from_color(Color::hsl(0.0, 0.0, 0.4)));
+    let doomed_material =
+        material_assets.add(StandardMaterial::from_color(Color::hsl(8.0, 0.2, 0.35)));
 
     let spawn_volume = Sphere::new(10.0);
 
     let mut rng = rand::thread_rng();
-    for state in &graph.states {
+    for (id, annotation) in graph.annotations().into_iter().enumerate() {
         let position: Vec3 = spawn_volume.sample_boundary(&mut rng);
+        let material = match annotation.doomed {
+            true => &doomed_material,
+            false => &material,
+        };
         commands.spawn(GraphNodeBundle {
             mesh: mesh.clone().into(),
             material: material.clone().into(),
             transform: Transform::from_translation(position),
             colider: Collider::sphere(0.5),
-            data: GraphNodeData(Arc::clone(state)),
+            data: GraphNodeData(id as solver::StateId),
             velocity: GraphNodeVelocity(Vec3::ZERO),
         });
     }
@@ -96,9 +100,15 @@ fn setup_nodes(
 
 fn setup_edges(mut commands: Commands, nodes: Query<(Entity, &GraphNodeData)>, graph: Res<Graph>) {
     let graph = &graph.into_inner().0;
-    for (n1, n2) in graph.edges.iter() {
-        let (entity1, _) = nodes.iter().find(|(_, data)| data.0 == *n1).unwrap();
-        let (entity2, _) = nodes.iter().find(|(_, data)| data.0 == *n2).unwrap();
+    for edge in graph.edges() {
+        let (entity1, _) = nodes
+            .iter()
+            .find(|(_, data)| data.0 == edge.source)
+            .unwrap();
+        let (entity2, _) = nodes
+            .iter()
+            .find(|(_, data)| data.0 == edge.target)
+            .unwrap();
         commands.spawn(GraphEdgeBundle {
             neighbors: GraphEdgeNeighbors(entity1, entity2),
         });
@@ -242,7 +252,7 @@ struct GraphNodeBundle {
 struct GraphNodeVelocity(Vec3);
 
 #[derive(Component)]
-struct GraphNodeData(Arc<solver::LevelState>);
+struct GraphNodeData(solver::StateId);
 
 #[derive(Bundle)]
 struct GraphEdgeBundle {
@@ -1,3 +1,2 @@
-#![feature(hash_set_entry)]
 pub mod graph;
 pub mod solver;
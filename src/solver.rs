@@ -3,31 +3,82 @@ use serde::{ser::SerializeMap, Deserialize, Serialize};
 use std::{
     backtrace,
     cell::OnceCell,
-    collections::{HashSet, VecDeque},
-    hash::{DefaultHasher, Hash, Hasher},
-    sync::{Arc, Weak},
+    collections::{HashMap, HashSet, VecDeque},
+    hash::Hash,
+    ops::ControlFlow,
+    sync::{Arc, OnceLock},
 };
 use wasm_bindgen::prelude::*;
 
+pub mod ascii;
+mod astar;
+mod packed;
+mod push;
 #[cfg(test)]
 mod test;
 
 use bevy::math::IVec3;
 
+pub use astar::{astar_solution, heuristic};
+use push::Push;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum TileType {
     Ground,
     Grill,
+    Ladder,
     Water,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct LevelDescription {
     start_pos: IVec3,
     start_dir: IVec3,
     ground: HashSet<IVec3>,
     grills: HashSet<IVec3>,
+    /// Ladders are terrain the player climbs by walking into them and the
+    /// fork can reach through, for everything else they are like ground.
+    #[serde(default)]
+    ladders: HashSet<IVec3>,
     sausages: Vec<Sausage>,
+    #[serde(default, deserialize_with = "rules_or_default")]
+    rules: Ruleset,
+}
+
+/// Reads rules that are null or undefined, as the front-end sends for a level
+/// without any, as the default rules.
+fn rules_or_default<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Ruleset, D::Error> {
+    Ok(Option::<Ruleset>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// Variations on the rules of the game a level can be played by, to compare
+/// how they change the same level. The default is the game's own rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Ruleset {
+    /// The level is only solved with the player back where they started,
+    /// facing the way they started.
+    pub return_to_start: bool,
+    /// How often a face can be cooked before it burns.
+    pub max_cooked: u8,
+    /// Stepping onto a grill burns the player, who hops back off it.
+    pub grill_bounce: bool,
+    /// Sausages fall off edges, otherwise a move that would leave any
+    /// sausage unsupported does nothing.
+    pub sausages_fall: bool,
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        Ruleset {
+            return_to_start: true,
+            max_cooked: 1,
+            grill_bounce: true,
+            sausages_fall: true,
+        }
+    }
 }
 
 impl LevelDescription {
@@ -38,15 +89,165 @@ impl LevelDescription {
         if self.grills.contains(&tile) {
             return TileType::Grill;
         }
+        if self.ladders.contains(&tile) {
+            return TileType::Ladder;
+        }
         TileType::Water
     }
+
+    /// The rules the level is played by.
+    pub fn rules(&self) -> Ruleset {
+        self.rules
+    }
+
+    /// The same level played by `rules` instead.
+    pub fn with_rules(&self, rules: Ruleset) -> LevelDescription {
+        LevelDescription {
+            rules,
+            ..self.clone()
+        }
+    }
+
+    /// Everything wrong with the level, in a deterministic order. A level
+    /// without problems can be played and solved.
+    pub fn validate(&self) -> Vec<LevelProblem> {
+        let mut problems = Vec::new();
+
+        let mut both: Vec<IVec3> = self.ground.intersection(&self.grills).copied().collect();
+        both.sort_by_key(|tile| tile.to_array());
+        problems.extend(
+            both.into_iter()
+                .map(|tile| LevelProblem::GroundAndGrill { tile }),
+        );
+        let mut ladders: Vec<IVec3> = self
+            .ladders
+            .iter()
+            .filter(|tile| self.ground.contains(tile) || self.grills.contains(tile))
+            .copied()
+            .collect();
+        ladders.sort_by_key(|tile| tile.to_array());
+        problems.extend(
+            ladders
+                .into_iter()
+                .map(|tile| LevelProblem::LadderInTerrain { tile }),
+        );
+
+        if Move::from_direction(self.start_dir).is_none() {
+            problems.push(LevelProblem::InvalidStartDir {
+                dir: self.start_dir,
+            });
+        }
+        if self.get_tile_type(self.start_pos) != TileType::Water {
+            problems.push(LevelProblem::PlayerInTerrain {
+                tile: self.start_pos,
+            });
+        }
+        if !matches!(
+            self.get_tile_type(self.start_pos + IVec3::NEG_Z),
+            TileType::Ground | TileType::Ladder
+        ) {
+            problems.push(LevelProblem::PlayerNotOnGround {
+                tile: self.start_pos + IVec3::NEG_Z,
+            });
+        }
+
+        let mut occupied = HashSet::from([self.start_pos]);
+        for sausage in &self.sausages {
+            for tile in [sausage.pos, sausage.pos2()] {
+                if self.get_tile_type(tile) != TileType::Water {
+                    problems.push(LevelProblem::SausageInTerrain { tile });
+                }
+                if tile == self.start_pos {
+                    problems.push(LevelProblem::PlayerInSausage { tile });
+                } else if !occupied.insert(tile) {
+                    problems.push(LevelProblem::OverlappingSausages { tile });
+                }
+            }
+        }
+
+        problems
+    }
+}
+
+/// A reason a `LevelDescription` can not be played, found by
+/// `LevelDescription::validate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LevelProblem {
+    GroundAndGrill {
+        tile: IVec3,
+    },
+    /// a ladder on the same tile as ground or a grill
+    LadderInTerrain {
+        tile: IVec3,
+    },
+    /// the player has to face north, east, south or west
+    InvalidStartDir {
+        dir: IVec3,
+    },
+    PlayerInTerrain {
+        tile: IVec3,
+    },
+    /// `tile` is the one below the player
+    PlayerNotOnGround {
+        tile: IVec3,
+    },
+    PlayerInSausage {
+        tile: IVec3,
+    },
+    SausageInTerrain {
+        tile: IVec3,
+    },
+    OverlappingSausages {
+        tile: IVec3,
+    },
+}
+
+impl std::fmt::Display for LevelProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LevelProblem::GroundAndGrill { tile } => {
+                write!(f, "{tile} is both ground and a grill")
+            }
+            LevelProblem::LadderInTerrain { tile } => {
+                write!(f, "{tile} is both a ladder and ground or a grill")
+            }
+            LevelProblem::InvalidStartDir { dir } => {
+                write!(
+                    f,
+                    "the player starts facing {dir}, which is not north, east, south or west"
+                )
+            }
+            LevelProblem::PlayerInTerrain { tile } => {
+                write!(f, "the player starts inside the terrain at {tile}")
+            }
+            LevelProblem::PlayerNotOnGround { tile } => {
+                write!(
+                    f,
+                    "the player starts above {tile}, which is not ground or a ladder"
+                )
+            }
+            LevelProblem::PlayerInSausage { tile } => {
+                write!(f, "the player starts inside a sausage at {tile}")
+            }
+            LevelProblem::SausageInTerrain { tile } => {
+                write!(f, "a sausage is inside the terrain at {tile}")
+            }
+            LevelProblem::OverlappingSausages { tile } => {
+                write!(f, "sausages overlap at {tile}")
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
+#[wasm_bindgen]
 pub struct LevelState {
     player_pos: IVec3,
     player_dir: IVec3,
     sausages: Vec<Sausage>,
+    /// the index of the sausage lifted up on the fork
+    carried: Option<usize>,
     description: Arc<LevelDescription>,
 }
 
@@ -55,60 +256,88 @@ impl Serialize for LevelState {
     where
         S: serde::Serializer,
     {
-        let mut s = serializer.serialize_map(Some(5))?;
-        s.serialize_entry("id", &self.get_id());
-        s.serialize_entry("player_pos", &self.player_pos);
-        s.serialize_entry("player_dir", &self.player_dir);
-        s.serialize_entry("sausages", &self.sausages);
-        s.serialize_entry("status", &self.get_status());
+        let mut s = serializer.serialize_map(Some(6))?;
+        s.serialize_entry("fingerprint", &format!("{:016x}", self.fingerprint()))?;
+        s.serialize_entry("player_pos", &self.player_pos)?;
+        s.serialize_entry("player_dir", &self.player_dir)?;
+        s.serialize_entry("sausages", &self.sausages)?;
+        s.serialize_entry("carried", &self.carried)?;
+        s.serialize_entry("status", &self.get_status())?;
         s.end()
     }
 }
 
 impl From<&LevelDescription> for LevelState {
     fn from(value: &LevelDescription) -> Self {
-        LevelState {
-            player_pos: value.start_pos,
-            player_dir: value.start_dir,
-            sausages: value.sausages.clone(),
-            description: Arc::new(value.clone()),
-        }
+        LevelState::initial(Arc::new(value.clone()))
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LevelStatus {
     Unsolved,
     Lost,
     Solution,
     Burnt,
+    Drowned,
 }
 
-#[derive(Debug, Clone)]
-struct NodeNeighbors {
-    forward: Weak<LevelState>,
-    back: Weak<LevelState>,
-    right: Weak<LevelState>,
-    left: Weak<LevelState>,
+/// An input the player can give. The relative moves are interpreted against
+/// the direction the player is facing while the absolute moves are directions
+/// on the level grid, with north being towards negative y.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Move {
+    Forward,
+    Back,
+    Left,
+    Right,
+    North,
+    East,
+    South,
+    West,
 }
 
-impl Serialize for NodeNeighbors {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        fn weak_to_id(r: &Weak<LevelState>) -> u64 {
-            match r.upgrade() {
-                Some(state) => state.get_id(),
-                None => 0,
-            }
+impl Move {
+    pub const RELATIVE: [Move; 4] = [Move::Forward, Move::Back, Move::Left, Move::Right];
+    pub const ABSOLUTE: [Move; 4] = [Move::North, Move::East, Move::South, Move::West];
+
+    /// The direction on the level grid this move corresponds to for a player
+    /// facing `player_dir`.
+    pub fn direction(self, player_dir: IVec3) -> IVec3 {
+        match self {
+            Move::Forward => player_dir,
+            Move::Back => -player_dir,
+            Move::Left => player_dir.cross(IVec3::Z),
+            Move::Right => -player_dir.cross(IVec3::Z),
+            Move::North => IVec3::NEG_Y,
+            Move::East => IVec3::X,
+            Move::South => IVec3::Y,
+            Move::West => IVec3::NEG_X,
         }
-        let mut s = serializer.serialize_map(Some(4))?;
-        s.serialize_entry("forward", &weak_to_id(&self.forward));
-        s.serialize_entry("back", &weak_to_id(&self.back));
-        s.serialize_entry("right", &weak_to_id(&self.right));
-        s.serialize_entry("left", &weak_to_id(&self.left));
-        s.end()
+    }
+
+    /// The absolute move pointing along `dir`, if it is a unit horizontal
+    /// vector.
+    pub fn from_direction(dir: IVec3) -> Option<Move> {
+        Move::ABSOLUTE.into_iter().find(|m| m.direction(dir) == dir)
+    }
+
+    /// Converts between relative and absolute moves for a player facing
+    /// `player_dir`. Moves that have no equivalent, for example because
+    /// `player_dir` is not a unit horizontal vector, are returned unchanged.
+    pub fn to_absolute(self, player_dir: IVec3) -> Move {
+        Move::from_direction(self.direction(player_dir)).unwrap_or(self)
+    }
+
+    pub fn to_relative(self, player_dir: IVec3) -> Move {
+        let dir = self.direction(player_dir);
+        Move::RELATIVE
+            .into_iter()
+            .find(|m| m.direction(player_dir) == dir)
+            .unwrap_or(self)
     }
 }
 
@@ -117,6 +346,7 @@ impl Hash for LevelState {
         self.player_pos.hash(state);
         self.player_dir.hash(state);
         self.sausages.hash(state);
+        self.carried.hash(state);
     }
 }
 impl PartialEq for LevelState {
@@ -124,6 +354,7 @@ impl PartialEq for LevelState {
         self.player_pos == other.player_pos
             && self.player_dir == other.player_dir
             && self.sausages == other.sausages
+            && self.carried == other.carried
     }
 }
 impl Eq for LevelState {}
@@ -143,27 +374,37 @@ impl LevelState {
     fn get_status(&self) -> LevelStatus {
         let ground = &self.description.ground;
         let grills = &self.description.grills;
+        // the player did not walk into the water
+        if !self.supports(self.player_pos + IVec3::NEG_Z) {
+            return LevelStatus::Drowned;
+        }
         // no sausages Lost
         for sausage in &self.sausages {
             if sausage.pos.z < 1 {
                 return LevelStatus::Lost;
             }
         }
+        let rules = &self.description.rules;
         // no sausages burnt
         for sausage in &self.sausages {
-            if sausage.cooked[0][0] > 1
-                || sausage.cooked[0][1] > 1
-                || sausage.cooked[1][0] > 1
-                || sausage.cooked[1][1] > 1
+            if sausage
+                .cooked
+                .as_flattened()
+                .iter()
+                .any(|c| *c > rules.max_cooked)
             {
                 return LevelStatus::Burnt;
             }
         }
         // win?
-        if self.sausages.iter().all(|s| {
-            s.cooked[0][0] == 1 && s.cooked[0][1] == 1 && s.cooked[1][0] == 1 && s.cooked[1][1] == 1
-        }) && self.player_pos == self.description.start_pos
-            && self.player_dir == self.description.start_dir
+        if self
+            .sausages
+            .iter()
+            .all(|s| s.cooked.as_flattened().iter().all(|c| *c >= 1))
+            && (!rules.return_to_start
+                || self.player_pos == self.description.start_pos
+                    && self.player_dir == self.description.start_dir)
+            && self.carried.is_none()
         {
             return LevelStatus::Solution;
         }
@@ -171,258 +412,1135 @@ impl LevelState {
         LevelStatus::Unsolved
     }
 
+    /// Whether something can rest on `tile`, which is any terrain or a
+    /// sausage.
+    fn supports(&self, tile: IVec3) -> bool {
+        self.description.get_tile_type(tile) != TileType::Water || self.get_sausage(tile).is_some()
+    }
+
+    /// Where the player comes to rest after stepping into `tile`, falling
+    /// down until they stand on something or reach the water.
+    fn landing(&self, mut tile: IVec3) -> IVec3 {
+        while tile.z > 1 && !self.supports(tile + IVec3::NEG_Z) {
+            tile.z -= 1;
+        }
+        tile
+    }
+
+    /// Whether the fork can come down through `tile` while the player falls.
+    fn fork_can_pass(&self, tile: IVec3) -> bool {
+        matches!(
+            self.description.get_tile_type(tile),
+            TileType::Water | TileType::Ladder
+        ) && self.get_sausage(tile).is_none()
+    }
+
+    /// Like `can_push` but for the fork, which reaches through ladders.
+    fn fork_can_push(&self, pos: IVec3, dir: IVec3) -> bool {
+        self.description.get_tile_type(pos) == TileType::Ladder || self.can_push(pos, dir)
+    }
+
+    /// Whether whatever is at `pos` can be pushed towards `dir`, see
+    /// `push::Push`.
     fn can_push(&self, pos: IVec3, dir: IVec3) -> bool {
-        if self.description.ground.contains(&pos) || self.description.grills.contains(&pos) {
-            return false;
+        Push::resolve(self, pos, dir).is_some()
+    }
+
+    /// Pushes whatever is at `pos` towards `dir` if it can be pushed.
+    fn push(&mut self, pos: IVec3, dir: IVec3) {
+        if let Some(push) = Push::resolve(self, pos, dir) {
+            push.apply(&mut self.sausages);
+        }
+    }
+
+    /// Cooks the bottom face of every half that rests on a grill at the end
+    /// of a move in which its sausage moved, whether it was pushed, rolled or
+    /// fell. Sausages that stay where they are do not cook again.
+    fn cook(&mut self, before: &LevelState) {
+        for (sausage, old) in self.sausages.iter_mut().zip(&before.sausages) {
+            if sausage.pos == old.pos {
+                continue;
+            }
+            for (j, half) in [sausage.pos, sausage.pos2()].into_iter().enumerate() {
+                if self.description.grills.contains(&(half + IVec3::NEG_Z)) {
+                    sausage.cooked[0][j] = sausage.cooked[0][j].saturating_add(1);
+                }
+            }
+        }
+    }
+
+    /// Moves the player one tile forward, pushing whatever the fork runs into
+    /// if it can be pushed, and down any ledge they step off. Walking into a
+    /// ladder climbs it instead. Does nothing if the player or the fork would
+    /// run into terrain or the fork can not come down with them. A fork that
+    /// ends up right above a sausage without the player stepping down lifts
+    /// it.
+    fn walk_forward(&mut self) {
+        let ahead = self.player_pos + self.player_dir;
+        match self.description.get_tile_type(ahead) {
+            TileType::Ladder => return self.climb(ahead),
+            TileType::Ground | TileType::Grill => return,
+            TileType::Water => (),
+        }
+        let fork_target = ahead + self.player_dir;
+        let landing = self.landing(ahead);
+        if (landing.z..ahead.z).any(|z| !self.fork_can_pass(fork_target.with_z(z))) {
+            return;
         }
-        let (i, sausage_to_push) = match self
+        if landing == ahead
+            && matches!(
+                self.description.get_tile_type(fork_target),
+                TileType::Ground | TileType::Grill
+            )
+        {
+            return;
+        }
+        let lifting = landing == ahead && self.get_sausage(fork_target).is_none();
+        if self.can_push(fork_target, self.player_dir) {
+            self.push(fork_target, self.player_dir);
+        }
+        self.player_pos = landing;
+        if lifting {
+            self.lift(fork_target);
+        }
+    }
+
+    /// Lifts the sausage right below `fork` up onto the fork, if there is
+    /// room above it.
+    fn lift(&mut self, fork: IVec3) {
+        let Some(i) = self
             .sausages
             .iter()
-            .enumerate()
-            .find(|(i, s)| s.pos == pos || s.pos + IVec3::from(s.orientation) == pos)
-        {
-            Some(s) => s,
-            None => return true,
+            .position(|s| s.pos + IVec3::Z == fork || s.pos2() + IVec3::Z == fork)
+        else {
+            return;
         };
+        let sausage = &self.sausages[i];
+        if [sausage.pos, sausage.pos2()]
+            .into_iter()
+            .all(|half| self.is_free(half + IVec3::Z))
+        {
+            self.sausages[i].pos += IVec3::Z;
+            self.carried = Some(i);
+        }
+    }
 
-        if dir.dot(sausage_to_push.orientation.into()) == 0 {
-            //rolling
-            let tiles_to_clear = [
-                sausage_to_push.pos + dir,
-                sausage_to_push.pos + IVec3::from(sausage_to_push.orientation) + dir,
-            ];
-            self.can_push(tiles_to_clear[0], dir) && self.can_push(tiles_to_clear[1], dir)
-        } else {
-            //sliding
-            let sausage_pos = sausage_to_push.pos;
-            if dir == IVec3::from(sausage_to_push.orientation) {
-                self.can_push(sausage_pos + (dir * 2), dir)
-            } else {
-                self.can_push(sausage_pos + dir, dir)
+    /// The index of the sausage the fork is stuck in or carries, if any.
+    fn held(&self) -> Option<usize> {
+        let fork = self.player_pos + self.player_dir;
+        self.sausages
+            .iter()
+            .position(|s| s.pos == fork || s.pos2() == fork)
+    }
+
+    /// Moves the player and the sausage `i` on their fork one tile in `dir`
+    /// together, the sausage sliding without rolling and both pushing what is
+    /// in their way. Returns false without changing anything if something can
+    /// not be pushed or the player would step off a ledge.
+    fn move_with_fork(&mut self, i: usize, dir: IVec3) -> bool {
+        let mut next = self.clone();
+        let held = next.sausages.remove(i);
+        next.carried = None;
+        let target = self.player_pos + dir;
+        for tile in [target, held.pos + dir, held.pos2() + dir] {
+            if !next.can_push(tile, dir) {
+                return false;
             }
+            next.push(tile, dir);
+        }
+        if next.landing(target) != target {
+            return false;
         }
+        next.sausages.insert(
+            i,
+            Sausage {
+                pos: held.pos + dir,
+                ..held
+            },
+        );
+        next.player_pos = target;
+        next.carried = self.carried;
+        *self = next;
+        true
     }
 
-    fn push(&mut self, pos: IVec3, dir: IVec3) {
-        let (i, sausage_to_push) = match self
-            .sausages
-            .iter_mut()
-            .enumerate()
-            .find(|(i, s)| s.pos == pos || s.pos + IVec3::from(s.orientation) == pos)
-        {
-            Some(s) => s,
-            None => return,
+    /// Turns the player to face `side`, the fork sweeping through the tile
+    /// diagonally in front of them and then the tile beside them and pushing
+    /// what it meets out of its way. Does nothing, not even the first push,
+    /// if the fork runs into terrain or something it can not push.
+    fn turn(&mut self, side: IVec3) {
+        let mut next = self.clone();
+        let diagonal = self.player_pos + self.player_dir + side;
+        if !next.fork_can_push(diagonal, side) {
+            return;
+        }
+        next.push(diagonal, side);
+        let beside = self.player_pos + side;
+        if !next.fork_can_push(beside, -self.player_dir) {
+            return;
+        }
+        next.push(beside, -self.player_dir);
+        next.player_dir = side;
+        *self = next;
+    }
+
+    /// Turns the player to face `dir`, swinging the sausage `i` lifted up on
+    /// the fork around with them. Each half sweeps sideways and then back
+    /// towards the player one tile at a time, like the fork does in `turn`,
+    /// pushing what it meets the way it moves. Does nothing, not even the
+    /// first push, if a half sweeps into terrain or something it can not
+    /// push.
+    fn swing(&mut self, i: usize, dir: IVec3) {
+        let mut next = self.clone();
+        let sausage = next.sausages.remove(i);
+        next.carried = None;
+        let left = dir == self.player_dir.cross(IVec3::Z);
+        let turn = |tile: IVec3| {
+            let offset = (tile - self.player_pos).cross(IVec3::Z);
+            self.player_pos + if left { offset } else { -offset }
         };
+        let halves = [sausage.pos, sausage.pos2()];
+        for half in halves {
+            let travel = turn(half) - half;
+            let sideways = (0..travel.dot(dir)).map(|_| dir);
+            let back = (0..-travel.dot(self.player_dir)).map(|_| -self.player_dir);
+            let mut tile = half;
+            for step in sideways.chain(back) {
+                tile += step;
+                if !next.can_push(tile, step) {
+                    return;
+                }
+                next.push(tile, step);
+            }
+        }
+        let halves = halves.map(turn);
+        if !halves.iter().all(|half| next.is_free(*half)) {
+            return;
+        }
+        let pos = halves[0].min(halves[1]);
+        let mut cooked = sausage.cooked;
+        if pos != halves[0] {
+            for face in &mut cooked {
+                face.swap(0, 1);
+            }
+        }
+        next.sausages.insert(
+            i,
+            Sausage {
+                pos,
+                cooked,
+                orientation: match halves[0].x == halves[1].x {
+                    true => SausageOrientation::Vertical,
+                    false => SausageOrientation::Horizontal,
+                },
+            },
+        );
+        next.carried = self.carried;
+        next.player_dir = dir;
+        *self = next;
+    }
 
-        if dir.dot(sausage_to_push.orientation.into()) == 0 {
-            //rolling
-            let tiles_to_clear = [
-                sausage_to_push.pos + dir,
-                sausage_to_push.pos + IVec3::from(sausage_to_push.orientation) + dir,
-            ];
-            (sausage_to_push.cooked[0], sausage_to_push.cooked[1]) =
-                (sausage_to_push.cooked[1], sausage_to_push.cooked[0]);
-        } else {
-            //sliding
-            let sausage_pos = sausage_to_push.pos;
-            if dir == IVec3::from(sausage_to_push.orientation) {
-                self.push(sausage_pos + (dir * 2), dir);
-            } else {
-                self.push(sausage_pos + dir, dir);
-            };
+    /// Whether `tile` has no terrain, sausage or player in it.
+    fn is_free(&self, tile: IVec3) -> bool {
+        self.description.get_tile_type(tile) == TileType::Water
+            && self.get_sausage(tile).is_none()
+            && tile != self.player_pos
+    }
+
+    /// Climbs the ladder at `ladder` to stand on top of it, with the fork
+    /// reaching over whatever the ladder leans against. Does nothing if the
+    /// fork would end up in terrain or can not push what is there.
+    fn climb(&mut self, ladder: IVec3) {
+        let mut top = ladder;
+        while self.description.get_tile_type(top) == TileType::Ladder {
+            top += IVec3::Z;
         }
-        self.sausages[i].pos += dir;
-        if self
-            .description
-            .grills
-            .contains(&(self.sausages[i].pos + IVec3::NEG_Z))
+        if self.description.get_tile_type(top) != TileType::Water || self.get_sausage(top).is_some()
         {
-            self.sausages[i].cooked[0][0] += 1
+            return;
         }
-        if self.description.grills.contains(
-            &(self.sausages[i].pos + IVec3::from(self.sausages[i].orientation) + IVec3::NEG_Z),
-        ) {
-            self.sausages[i].cooked[0][1] += 1
+        let fork_target = top + self.player_dir;
+        if !self.fork_can_push(fork_target, self.player_dir) {
+            return;
+        }
+        self.push(fork_target, self.player_dir);
+        self.player_pos = top;
+    }
+
+    /// Moves the player one tile back, pushing what is behind them, and down
+    /// any ledge they step off. Does nothing if what is behind can not be
+    /// pushed or the fork can not follow the player down.
+    fn walk_back(&mut self) {
+        let behind = self.player_pos - self.player_dir;
+        let landing = self.landing(behind);
+        if !self.can_push(behind, -self.player_dir)
+            || (landing.z..behind.z).any(|z| !self.fork_can_pass(self.player_pos.with_z(z)))
+        {
+            return;
         }
+        self.push(behind, -self.player_dir);
+        self.player_pos = landing;
     }
 
-    fn get_next_state(&self, input: IVec3) -> LevelState {
+    fn get_next_state(&self, m: Move) -> LevelState {
         let mut state = self.clone();
+        let input = m.direction(self.player_dir);
+        let left = self.player_dir.cross(IVec3::Z);
+        let held = self.held();
 
-        match input {
-            IVec3 { x: 1, y: 0, z: 0 } => (),
-            IVec3 { x: 0, y: 1, z: 0 } => (),
-            IVec3 { x: -1, y: 0, z: 0 } => (),
-            IVec3 { x: 0, y: -1, z: 0 } => (),
-            _ => panic!("get_next_state was supplied a vector that was not a valid input"),
-        };
+        if let Some(i) = self.carried {
+            // a lifted sausage swings around with the player and is put down
+            // by stepping back
+            if input == self.player_dir {
+                state.move_with_fork(i, input);
+            } else if input == -self.player_dir {
+                state.walk_back();
+                if state.player_pos != self.player_pos {
+                    state.carried = None;
+                }
+            } else if input == left || input == -left {
+                state.swing(i, input);
+            }
+        } else if let Some(i) = held {
+            // a sausage stuck on the fork keeps the player from turning, it
+            // moves along with them and comes off when it can not follow them
+            // back
+            if !state.move_with_fork(i, input) && input == -self.player_dir {
+                state.walk_back();
+            }
+        } else if input == self.player_dir {
+            state.walk_forward();
+        } else if input == -self.player_dir {
+            state.walk_back();
+        } else if input == left || input == -left {
+            state.turn(input);
+        }
 
-        if self.player_dir == input
-            && (self
+        // the grill burns the player, who hops back where they came from with
+        // the fork and whatever is on it
+        if state.description.rules.grill_bounce
+            && state.player_pos != self.player_pos
+            && state
                 .description
-                .get_tile_type(self.player_pos + input + IVec3::NEG_Z)
-                == TileType::Ground
-                || self
-                    .description
-                    .get_tile_type(self.player_pos + input + IVec3::NEG_Z)
-                    == TileType::Grill)
+                .get_tile_type(state.player_pos + IVec3::NEG_Z)
+                == TileType::Grill
         {
-            if state.can_push(state.player_pos + (state.player_dir * 2), state.player_dir) {
-                state.push(state.player_pos + (state.player_dir * 2), state.player_dir);
+            state.player_pos = self.player_pos;
+            state.carried = self.carried;
+            if let Some(i) = held {
+                state.sausages[i].pos = self.sausages[i].pos;
             }
-            state.player_pos += state.player_dir;
         }
-        if -self.player_dir == input
-            && (self
-                .description
-                .get_tile_type(self.player_pos - self.player_dir + IVec3::NEG_Z)
-                == TileType::Ground
-                || self
-                    .description
-                    .get_tile_type(self.player_pos - self.player_dir + IVec3::NEG_Z)
-                    == TileType::Grill)
-            && state.can_push(state.player_pos - state.player_dir, -state.player_dir)
+
+        // sausages fall until they land in the water or either half rests on
+        // something, a sausage hanging off an edge by one half stays put
+        let unsupported = |state: &LevelState, i: usize| {
+            let sausage = &state.sausages[i];
+            // the fork holds a lifted sausage up
+            state.carried != Some(i)
+                && sausage.pos.z >= 1
+                && ![sausage.pos, sausage.pos2()].into_iter().any(|half| {
+                    state.supports(half + IVec3::NEG_Z) || half + IVec3::NEG_Z == state.player_pos
+                })
+        };
+        if !state.description.rules.sausages_fall
+            && (0..state.sausages.len()).any(|i| unsupported(&state, i))
         {
-            state.push(state.player_pos - state.player_dir, -state.player_dir);
-            state.player_pos -= state.player_dir;
+            return self.clone();
+        }
+        let mut falling = true;
+        while falling {
+            falling = false;
+            for i in 0..state.sausages.len() {
+                if unsupported(&state, i) {
+                    state.sausages[i].pos += IVec3::NEG_Z;
+                    falling = true;
+                }
+            }
+        }
+        // the player falls when what they stood on moved away
+        state.player_pos = state.landing(state.player_pos);
+
+        state.cook(self);
+        state
+    }
+}
+
+/// Lets the front-end play a level one move at a time using the same rules
+/// the solver explores the level with.
+#[wasm_bindgen]
+impl LevelState {
+    /// The initial state of the level described by a `LevelDescription`
+    /// object, which is validated like in `solve`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(level_description: JsValue) -> Result<LevelState, JsValue> {
+        console_error_panic_hook::set_once();
+
+        Ok(LevelState::from(&parse_level_description(
+            level_description,
+        )?))
+    }
+
+    /// The state the level is in after the player gives the input `m`.
+    /// Inputs that the player can not act on leave the state unchanged.
+    pub fn step(&self, m: Move) -> LevelState {
+        self.get_next_state(m)
+    }
+
+    pub fn player_pos(&self) -> Vec<i32> {
+        self.player_pos.to_array().to_vec()
+    }
+
+    pub fn player_dir(&self) -> Vec<i32> {
+        self.player_dir.to_array().to_vec()
+    }
+
+    pub fn sausages(&self) -> Vec<Sausage> {
+        self.sausages.clone()
+    }
+
+    /// The index of the sausage lifted up on the fork, if any.
+    pub fn carried(&self) -> Option<usize> {
+        self.carried
+    }
+
+    pub fn status(&self) -> LevelStatus {
+        self.get_status()
+    }
+}
+
+impl LevelState {
+    fn initial(description: Arc<LevelDescription>) -> LevelState {
+        LevelState {
+            player_pos: description.start_pos,
+            player_dir: description.start_dir,
+            sausages: description.sausages.clone(),
+            carried: None,
+            description,
+        }
+    }
+
+    /// A hash of the player and sausages that only depends on the state
+    /// itself, so it is the same across builds and platforms and can be
+    /// computed without a graph. Distinct states may share a fingerprint, use
+    /// the id a `LevelGraph` assigns to tell states apart.
+    pub fn fingerprint(&self) -> u64 {
+        // 64 bit FNV-1a over a fixed little endian encoding of the state
+        fn write(hash: u64, bytes: &[u8]) -> u64 {
+            bytes.iter().fold(hash, |hash, b| {
+                (hash ^ *b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+            })
+        }
+        fn write_vec(hash: u64, v: IVec3) -> u64 {
+            v.to_array()
+                .iter()
+                .fold(hash, |hash, c| write(hash, &c.to_le_bytes()))
+        }
+
+        let mut hash = 0xcbf2_9ce4_8422_2325;
+        hash = write_vec(hash, self.player_pos);
+        hash = write_vec(hash, self.player_dir);
+        for sausage in &self.sausages {
+            hash = write_vec(hash, sausage.pos);
+            hash = write(
+                hash,
+                &[match sausage.orientation {
+                    SausageOrientation::Horizontal => 0,
+                    SausageOrientation::Vertical => 1,
+                }],
+            );
+            hash = write(hash, sausage.cooked.as_flattened());
+        }
+        if let Some(carried) = self.carried {
+            hash = write(hash, &(carried as u32).to_le_bytes());
         }
-        if self.player_dir.cross(IVec3::Z) == input {
-            let left = self.player_dir.cross(IVec3::Z);
-            if state.can_push(state.player_pos + state.player_dir + left, left) {
-                state.push(state.player_pos + state.player_dir + left, left);
+        hash
+    }
+}
+
+/// Index of a state in a `LevelGraph`. States are numbered in the order the
+/// breadth first search in `generate_graph` discovers them, starting with the
+/// initial state at 0, so the same level always produces the same ids.
+pub type StateId = u32;
 
-                if state.can_push(state.player_pos + left, -state.player_dir) {
-                    state.push(state.player_pos + left, -state.player_dir);
-                    state.player_dir = left;
+#[derive(Debug)]
+#[wasm_bindgen]
+pub struct LevelGraph {
+    pub(crate) description: Arc<LevelDescription>,
+    pub(crate) states: packed::StateStore,
+    /// targets of the edges leaving each explored state, in the order of
+    /// `Move::RELATIVE`
+    pub(crate) successors: Vec<Option<[StateId; 4]>>,
+    /// unexplored states that can still be played from, in the order they
+    /// were found. States explored out of order by `expand` are skipped when
+    /// they come up.
+    frontier: VecDeque<StateId>,
+    /// how many states on the frontier have not been explored yet
+    unexplored: usize,
+    explored: usize,
+    /// why exploration stopped early, `None` if every reachable state has
+    /// been explored
+    pub(crate) incomplete: Option<Incomplete>,
+    /// `annotations` for `hint`, cleared whenever the graph grows
+    annotation_cache: OnceLock<Vec<StateAnnotation>>,
+}
+
+impl LevelGraph {
+    /// A graph holding only the initial state of the level, which can then
+    /// be explored with `explore`, `expand_layers`, `expand_from` or
+    /// `expand`.
+    pub fn new(level_description: &LevelDescription) -> LevelGraph {
+        let description = Arc::new(level_description.clone());
+        let mut graph = LevelGraph {
+            states: packed::StateStore::new(packed::StateLayout::new(&description)),
+            successors: Vec::new(),
+            frontier: VecDeque::new(),
+            unexplored: 0,
+            explored: 0,
+            incomplete: None,
+            annotation_cache: OnceLock::new(),
+            description,
+        };
+        graph.insert_state(&LevelState::initial(Arc::clone(&graph.description)));
+        graph.update_incomplete(Incomplete::Unexplored);
+        graph
+    }
+
+    /// Adds `state` to the graph unless it is already in it. Returns the id of
+    /// the state and whether it was newly added, new states that can still be
+    /// played from are put on the frontier.
+    fn insert_state(&mut self, state: &LevelState) -> (StateId, bool) {
+        let (id, is_new) = self.states.insert(state);
+        if is_new {
+            self.annotation_cache.take();
+            self.successors.push(None);
+            if state.get_status() == LevelStatus::Unsolved {
+                self.frontier.push_back(id);
+                self.unexplored += 1;
+            }
+        }
+        (id, is_new)
+    }
+
+    /// Generates the successors of an unexplored state.
+    fn explore_state(&mut self, id: StateId) -> [StateId; 4] {
+        let state = self.states.get(id, &self.description);
+        let mut targets = [0; 4];
+        for (m, target) in Move::RELATIVE.into_iter().zip(&mut targets) {
+            (*target, _) = self.insert_state(&state.get_next_state(m));
+        }
+        self.successors[id as usize] = Some(targets);
+        self.annotation_cache.take();
+        self.explored += 1;
+        self.unexplored -= 1;
+        targets
+    }
+
+    /// Marks the graph incomplete for `reason` if any states on the frontier
+    /// are left to explore.
+    fn update_incomplete(&mut self, reason: Incomplete) {
+        self.incomplete = match self.unexplored {
+            0 => None,
+            _ => Some(reason),
+        };
+    }
+
+    /// The successors of `id` in the order of `Move::RELATIVE`, exploring it
+    /// first if needed. `None` if `id` is not in the graph or the game is
+    /// over in that state.
+    pub fn expand(&mut self, id: StateId) -> Option<[StateId; 4]> {
+        if let Some(targets) = *self.successors.get(id as usize)? {
+            return Some(targets);
+        }
+        if self.states.get(id, &self.description).get_status() != LevelStatus::Unsolved {
+            return None;
+        }
+        let targets = self.explore_state(id);
+        self.update_incomplete(Incomplete::Unexplored);
+        Some(targets)
+    }
+
+    /// Explores the next `layers` breadth first layers of the frontier and
+    /// returns how many states were added.
+    pub fn expand_layers(&mut self, layers: u32) -> usize {
+        let before = self.state_count();
+        let budget = SearchBudget {
+            max_depth: Some(layers),
+            ..Default::default()
+        };
+        self.explore(&budget, |_| ControlFlow::Continue(()));
+        self.state_count() - before
+    }
+
+    /// Explores every state within `layers` moves of `id`, leaving the rest
+    /// of the frontier alone, and returns how many states were added.
+    pub fn expand_from(&mut self, id: StateId, layers: u32) -> usize {
+        let before = self.state_count();
+        let mut visited = HashSet::from([id]);
+        let mut exploration_queue = VecDeque::from([(id, 0)]);
+        while let Some((current_id, depth)) = exploration_queue.pop_front() {
+            if depth >= layers {
+                continue;
+            }
+            let Some(targets) = self.expand(current_id) else {
+                continue;
+            };
+            for target in targets {
+                if visited.insert(target) {
+                    exploration_queue.push_back((target, depth + 1));
                 }
             }
         }
-        if -self.player_dir.cross(IVec3::Z) == input {
-            let right = -self.player_dir.cross(IVec3::Z);
-            if state.can_push(state.player_pos + state.player_dir + right, right) {
-                state.push(state.player_pos + state.player_dir + right, right);
+        self.state_count() - before
+    }
 
-                if state.can_push(state.player_pos + right, -state.player_dir) {
-                    state.push(state.player_pos + right, -state.player_dir);
-                    state.player_dir = right;
+    /// The successors of `id` in the order of `Move::RELATIVE`, or `None` if
+    /// it has not been explored.
+    pub fn successors(&self, id: StateId) -> Option<[StateId; 4]> {
+        *self.successors.get(id as usize)?
+    }
+
+    pub fn level_description(&self) -> &LevelDescription {
+        &self.description
+    }
+
+    pub fn state_count(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Why exploration stopped before every reachable state was explored, or
+    /// `None` if the graph is complete.
+    pub fn incomplete(&self) -> Option<Incomplete> {
+        self.incomplete
+    }
+
+    pub fn initial_state(&self) -> LevelState {
+        self.states.get(0, &self.description)
+    }
+
+    pub fn state(&self, id: StateId) -> Option<LevelState> {
+        ((id as usize) < self.state_count()).then(|| self.states.get(id, &self.description))
+    }
+
+    pub fn state_id(&self, state: &LevelState) -> Option<StateId> {
+        self.states.find(state)
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = Edge> + '_ {
+        self.successors
+            .iter()
+            .enumerate()
+            .filter_map(|(source, targets)| Some((source as StateId, (*targets)?)))
+            .flat_map(|(source, targets)| {
+                Move::RELATIVE
+                    .into_iter()
+                    .zip(targets)
+                    .map(move |(movement, target)| Edge {
+                        source,
+                        target,
+                        movement,
+                    })
+            })
+    }
+
+    /// Approximate number of bytes the graph keeps allocated on the heap.
+    pub fn heap_size(&self) -> usize {
+        self.states.heap_size()
+            + self.successors.capacity() * std::mem::size_of::<Option<[StateId; 4]>>()
+    }
+}
+
+/// A transition between two states of a level and the move that caused it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Edge {
+    pub source: StateId,
+    pub target: StateId,
+    pub movement: Move,
+}
+
+#[wasm_bindgen]
+impl LevelGraph {
+    /// Every edge of the graph as `{ source, target, movement }` objects where
+    /// `source` and `target` are state ids.
+    #[wasm_bindgen(js_name = edges)]
+    pub fn edges_js(&self) -> Result<JsValue, JsError> {
+        self.edges()
+            .collect::<Vec<Edge>>()
+            .serialize(&js_serializer())
+            .map_err(|e| JsError::new(&format!("error serializing edges: {e:?}")))
+    }
+
+    /// The edges leaving state `id`, exploring it first if needed. Empty if
+    /// the game is over in that state.
+    #[wasm_bindgen(js_name = expand)]
+    pub fn expand_js(&mut self, id: StateId) -> Result<JsValue, JsError> {
+        self.expand(id)
+            .into_iter()
+            .flat_map(|targets| Move::RELATIVE.into_iter().zip(targets))
+            .map(|(movement, target)| Edge {
+                source: id,
+                target,
+                movement,
+            })
+            .collect::<Vec<Edge>>()
+            .serialize(&js_serializer())
+            .map_err(|e| JsError::new(&format!("error serializing edges: {e:?}")))
+    }
+
+    #[wasm_bindgen(js_name = expand_layers)]
+    pub fn expand_layers_js(&mut self, layers: u32) -> usize {
+        self.expand_layers(layers)
+    }
+
+    #[wasm_bindgen(js_name = expand_from)]
+    pub fn expand_from_js(&mut self, id: StateId, layers: u32) -> usize {
+        self.expand_from(id, layers)
+    }
+
+    /// The best move from `state` as a serialized `Hint`, see `hint`.
+    #[wasm_bindgen(js_name = hint)]
+    pub fn hint_js(&self, state: &LevelState) -> Result<JsValue, JsError> {
+        serialize_hint(hint(state, Some(self)))
+    }
+
+    /// The whole graph as a plain object in its serialized form.
+    pub fn to_js(&self) -> Result<JsValue, JsError> {
+        self.serialize(&js_serializer())
+            .map_err(|e| JsError::new(&format!("error serializing graph: {e:?}")))
+    }
+
+    /// Rebuilds a graph from the object produced by `to_js`.
+    pub fn from_js(value: JsValue) -> Result<LevelGraph, JsError> {
+        serde_wasm_bindgen::from_value(value)
+            .map_err(|e| JsError::new(&format!("error parsing graph: {e:?}")))
+    }
+}
+
+/// The best move from `state` as a serialized `Hint`, without a graph to
+/// look it up in. See `hint`.
+#[wasm_bindgen(js_name = hint)]
+pub fn hint_js(state: &LevelState) -> Result<JsValue, JsError> {
+    console_error_panic_hook::set_once();
+
+    serialize_hint(hint(state, None))
+}
+
+fn serialize_hint(hint: Hint) -> Result<JsValue, JsError> {
+    hint.serialize(&js_serializer())
+        .map_err(|e| JsError::new(&format!("error serializing hint: {e:?}")))
+}
+
+fn js_serializer() -> serde_wasm_bindgen::Serializer {
+    serde_wasm_bindgen::Serializer::json_compatible()
+}
+
+/// A shortest sequence of moves taking the initial state of a level to a
+/// solved state.
+#[derive(Debug, Clone)]
+pub struct Solution {
+    pub moves: Vec<Move>,
+    /// every state along the way, starting with the initial state and ending
+    /// with the solved one, so there is always one more state than moves
+    pub states: Vec<LevelState>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ShortestSolution {
+    Solved(Solution),
+    /// no solved state is reachable from the initial state
+    Unsolvable,
+    /// the graph is incomplete and none of the states explored so far lead
+    /// to a solution
+    Inconclusive,
+}
+
+impl LevelGraph {
+    pub fn shortest_solution(&self) -> ShortestSolution {
+        let mut parents: Vec<Option<(StateId, Move)>> = vec![None; self.state_count()];
+        let mut exploration_queue: VecDeque<StateId> = VecDeque::new();
+        exploration_queue.push_back(0);
+
+        while let Some(current_id) = exploration_queue.pop_front() {
+            let current_state = self.states.get(current_id, &self.description);
+            match current_state.get_status() {
+                LevelStatus::Solution => {
+                    return ShortestSolution::Solved(trace_solution(&parents, current_id, |id| {
+                        self.states.get(id, &self.description)
+                    }));
                 }
+                LevelStatus::Lost | LevelStatus::Burnt | LevelStatus::Drowned => continue,
+                LevelStatus::Unsolved => (),
+            }
+
+            let Some(targets) = self.successors[current_id as usize] else {
+                continue;
+            };
+            for (m, target) in Move::RELATIVE.into_iter().zip(targets) {
+                if target == 0 || parents[target as usize].is_some() {
+                    continue;
+                }
+                parents[target as usize] = Some((current_id, m));
+                exploration_queue.push_back(target);
             }
         }
 
-        if self
-            .description
-            .grills
-            .contains(&(state.player_pos + IVec3::NEG_Z))
-        {
-            state.player_pos -= input
+        match self.incomplete {
+            Some(_) => ShortestSolution::Inconclusive,
+            None => ShortestSolution::Unsolvable,
         }
+    }
+}
 
-        for i in 0..state.sausages.len() {
-            let sausage = state.sausages.get(i).unwrap();
-            if !(state
-                .description
-                .ground
-                .contains(&(sausage.pos + IVec3::NEG_Z))
-                || state
-                    .description
-                    .grills
-                    .contains(&(sausage.pos + IVec3::NEG_Z))
-                || state.get_sausage(sausage.pos + IVec3::NEG_Z).is_some()
-                || state
-                    .description
-                    .ground
-                    .contains(&(sausage.pos2() + IVec3::NEG_Z))
-                || state
-                    .description
-                    .grills
-                    .contains(&(sausage.pos2() + IVec3::NEG_Z))
-                || state.get_sausage(sausage.pos2() + IVec3::NEG_Z).is_some())
-            {
-                let mut sausage = state.sausages.get_mut(i).unwrap();
-                sausage.pos += IVec3::NEG_Z;
+/// How close a state of a graph is to being solved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct StateAnnotation {
+    /// the fewest moves from the state to a solved one, only counting paths
+    /// through explored states if the graph is incomplete
+    pub distance: Option<u32>,
+    /// no solved state can be reached from the state, not even through
+    /// states that are yet to be explored. Lost and burnt states are doomed
+    /// too.
+    pub doomed: bool,
+}
+
+impl LevelGraph {
+    /// Annotates every state, in id order, by walking the edges backwards
+    /// from the solved states and from the states that are yet to be
+    /// explored.
+    pub fn annotations(&self) -> Vec<StateAnnotation> {
+        let state_count = self.state_count();
+
+        // the sources of the edges into each state, stored back to back
+        let mut offsets = vec![0; state_count + 1];
+        for target in self.successors.iter().flatten().flatten() {
+            offsets[*target as usize + 1] += 1;
+        }
+        for id in 0..state_count {
+            offsets[id + 1] += offsets[id];
+        }
+        let mut predecessors = vec![0; offsets[state_count]];
+        let mut next_slot = offsets.clone();
+        for (source, targets) in self.successors.iter().enumerate() {
+            for target in targets.iter().flatten() {
+                predecessors[next_slot[*target as usize]] = source as StateId;
+                next_slot[*target as usize] += 1;
             }
         }
+        let predecessors_of =
+            |id: StateId| &predecessors[offsets[id as usize]..offsets[id as usize + 1]];
 
-        state
+        let mut distances: Vec<Option<u32>> = vec![None; state_count];
+        let mut solved_queue = VecDeque::new();
+        // states that might lead to a solution through unexplored states
+        let mut open = vec![false; state_count];
+        let mut open_stack = Vec::new();
+        for id in 0..state_count as StateId {
+            match self.states.get(id, &self.description).get_status() {
+                LevelStatus::Solution => {
+                    distances[id as usize] = Some(0);
+                    solved_queue.push_back(id);
+                }
+                LevelStatus::Unsolved if self.successors[id as usize].is_none() => {
+                    open[id as usize] = true;
+                    open_stack.push(id);
+                }
+                _ => (),
+            }
+        }
+
+        while let Some(id) = solved_queue.pop_front() {
+            let distance = distances[id as usize].map(|d| d + 1);
+            for source in predecessors_of(id) {
+                if distances[*source as usize].is_none() {
+                    distances[*source as usize] = distance;
+                    solved_queue.push_back(*source);
+                }
+            }
+        }
+        while let Some(id) = open_stack.pop() {
+            for source in predecessors_of(id) {
+                if !open[*source as usize] {
+                    open[*source as usize] = true;
+                    open_stack.push(*source);
+                }
+            }
+        }
+
+        distances
+            .into_iter()
+            .zip(open)
+            .map(|(distance, open)| StateAnnotation {
+                distance,
+                doomed: distance.is_none() && !open,
+            })
+            .collect()
     }
 }
 
-impl LevelState {
-    pub fn get_id(&self) -> u64 {
-        let mut hasher = DefaultHasher::new();
-        self.hash(&mut hasher);
-        hasher.finish()
+/// What a player should do next, see `hint`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Hint {
+    /// the first move of a shortest path to a solution
+    Move(Move),
+    /// the state is a solution already
+    Solved,
+    /// no solution can be reached from the state, either because the game is
+    /// over in it or because every way forward ends up lost or burnt, which is
+    /// reported as `LevelStatus::Unsolved`
+    Unwinnable(LevelStatus),
+    /// the search gave up before finding a solution
+    Unknown,
+}
+
+/// How many states `hint` explores before giving up when the graph does not
+/// already answer the question.
+pub const HINT_SEARCH_STATES: usize = 200_000;
+
+/// The best move from `state`, which does not have to be reachable from the
+/// initial state of its level. `graph` is used if it is a graph of the same
+/// level that knows the answer, otherwise a search of at most
+/// `HINT_SEARCH_STATES` states starts from `state`.
+pub fn hint(state: &LevelState, graph: Option<&LevelGraph>) -> Hint {
+    match state.get_status() {
+        LevelStatus::Solution => return Hint::Solved,
+        status @ (LevelStatus::Lost | LevelStatus::Burnt | LevelStatus::Drowned) => {
+            return Hint::Unwinnable(status)
+        }
+        LevelStatus::Unsolved => (),
+    }
+
+    if let Some(hint) = graph
+        .filter(|graph| graph.description == state.description)
+        .and_then(|graph| graph.hint(state))
+    {
+        return hint;
+    }
+
+    let budget = SearchBudget {
+        max_states: Some(HINT_SEARCH_STATES),
+        ..Default::default()
+    };
+    match astar::astar_solution_from(state.clone(), &budget).0 {
+        ShortestSolution::Solved(solution) => Hint::Move(solution.moves[0]),
+        ShortestSolution::Unsolvable => Hint::Unwinnable(LevelStatus::Unsolved),
+        ShortestSolution::Inconclusive => Hint::Unknown,
     }
 }
 
-#[derive(Debug)]
-#[wasm_bindgen]
-pub struct LevelGraph {
-    pub(crate) states: HashSet<Arc<LevelState>>,
-    pub(crate) edges: Vec<(Arc<LevelState>, Arc<LevelState>)>,
-    pub(crate) initial_state: Arc<LevelState>,
-    pub(crate) level_description: LevelDescription,
+impl LevelGraph {
+    /// The hint for a state of this graph that can still be played, or
+    /// `None` if the graph does not hold the state or is too incomplete to
+    /// tell.
+    fn hint(&self, state: &LevelState) -> Option<Hint> {
+        let id = self.state_id(state)?;
+        let annotations = self.annotation_cache.get_or_init(|| self.annotations());
+        let annotation = annotations[id as usize];
+        if annotation.doomed {
+            return Some(Hint::Unwinnable(LevelStatus::Unsolved));
+        }
+        let distance = annotation.distance?;
+        Move::RELATIVE
+            .into_iter()
+            .zip(self.successors(id)?)
+            .find(|(_, target)| annotations[*target as usize].distance == Some(distance - 1))
+            .map(|(m, _)| Hint::Move(m))
+    }
+}
+
+/// Follows `parents` from the solved state `id` back to the state without a
+/// parent.
+fn trace_solution(
+    parents: &[Option<(StateId, Move)>],
+    mut id: StateId,
+    get_state: impl Fn(StateId) -> LevelState,
+) -> Solution {
+    let mut moves = Vec::new();
+    let mut states = vec![get_state(id)];
+    while let Some((parent, m)) = parents[id as usize] {
+        moves.push(m);
+        states.push(get_state(parent));
+        id = parent;
+    }
+    moves.reverse();
+    states.reverse();
+    Solution { moves, states }
 }
 
-/*
+/// The serialized form of a graph, as handed to the front-end:
+///
+/// ```json
+/// {
+///     "states": [{
+///         "id", "fingerprint", "player_pos", "player_dir", "sausages", "carried",
+///         "status", "distance", "doomed"
+///     }],
+///     "edges": [{ "source", "target", "movement" }],
+///     "initial_state": { /* the same fields as every state */ },
+///     "level_description": { "start_pos", "start_dir", "ground", "grills", "ladders", "sausages", "rules" },
+///     "incomplete": null | "state_limit" | "edge_limit" | "depth_limit" | "cancelled" | "unexplored"
+/// }
+/// ```
+///
+/// States are listed in id order and edges refer to states by their `id`. A
+/// state either has no outgoing edges or one for every relative move. The
+/// fingerprint is written as a hex string since JavaScript numbers can not
+/// hold it. `distance` and `doomed` are the state's `StateAnnotation`. When
+/// deserializing, the `fingerprint`, `status`, `distance` and `doomed` of each
+/// state are ignored and recomputed.
 impl Serialize for LevelGraph {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        let mut s = serializer.serialize_map(Some(3))?;
+        #[derive(Serialize)]
+        struct SerializedState {
+            id: StateId,
+            #[serde(flatten)]
+            state: LevelState,
+            #[serde(flatten)]
+            annotation: StateAnnotation,
+        }
+
+        let annotations = self.annotations();
+        let mut s = serializer.serialize_map(Some(5))?;
         s.serialize_entry(
             "states",
-            &self
-                .states
-                .iter()
-                .map(|l| l.as_ref())
-                .collect::<Vec<&LevelState>>(),
-        );
-        #[derive(Serialize, Debug)]
-        struct Edge {
-            id: usize,
-            source: u64,
-            target: u64,
-            movement: &'static str,
-        }
-        let mut edges: Vec<Edge> = Vec::new();
-        self.states.iter().enumerate().for_each(|(i, s)| {
-            if let Some(n) = s.neighbors.get() {
-                edges.push(Edge {
-                    id: i * 4 + 1,
-                    source: s.get_id(),
-                    target: n.forward.upgrade().unwrap().get_id(),
-                    movement: "forward",
-                });
-                edges.push(Edge {
-                    id: i * 4 + 2,
-                    source: s.get_id(),
-                    target: n.back.upgrade().unwrap().get_id(),
-                    movement: "back",
-                });
-                edges.push(Edge {
-                    id: i * 4 + 3,
-                    source: s.get_id(),
-                    target: n.right.upgrade().unwrap().get_id(),
-                    movement: "right",
-                });
-                edges.push(Edge {
-                    id: i * 4 + 4,
-                    source: s.get_id(),
-                    target: n.left.upgrade().unwrap().get_id(),
-                    movement: "left",
-                });
-            }
-        });
-        s.serialize_entry("edges", &edges);
-        s.serialize_entry("initial_state", self.initial_state.as_ref());
-        s.serialize_entry("level_description", &self.level_description);
+            &(0..self.state_count() as StateId)
+                .map(|id| SerializedState {
+                    id,
+                    state: self.states.get(id, &self.description),
+                    annotation: annotations[id as usize],
+                })
+                .collect::<Vec<SerializedState>>(),
+        )?;
+        s.serialize_entry("edges", &self.edges().collect::<Vec<Edge>>())?;
+        s.serialize_entry(
+            "initial_state",
+            &SerializedState {
+                id: 0,
+                state: self.initial_state(),
+                annotation: annotations[0],
+            },
+        )?;
+        s.serialize_entry("level_description", self.description.as_ref())?;
+        s.serialize_entry("incomplete", &self.incomplete)?;
         s.end()
     }
 }
-*/
 
+impl<'de> Deserialize<'de> for LevelGraph {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct SerializedState {
+            id: StateId,
+            player_pos: IVec3,
+            player_dir: IVec3,
+            sausages: Vec<Sausage>,
+            #[serde(default)]
+            carried: Option<usize>,
+        }
+        #[derive(Deserialize)]
+        struct SerializedGraph {
+            states: Vec<SerializedState>,
+            edges: Vec<Edge>,
+            level_description: LevelDescription,
+            #[serde(default)]
+            incomplete: Option<Incomplete>,
+        }
+
+        let serialized = SerializedGraph::deserialize(deserializer)?;
+        let description = Arc::new(serialized.level_description);
+        let mut graph = LevelGraph {
+            states: packed::StateStore::new(packed::StateLayout::new(&description)),
+            successors: Vec::new(),
+            frontier: VecDeque::new(),
+            unexplored: 0,
+            explored: 0,
+            incomplete: serialized.incomplete,
+            annotation_cache: OnceLock::new(),
+            description,
+        };
+
+        for (i, s) in serialized.states.into_iter().enumerate() {
+            if s.id as usize != i {
+                return Err(serde::de::Error::custom(format!(
+                    "expected state id {i} but found {}",
+                    s.id
+                )));
+            }
+            if s.carried.is_some_and(|carried| carried >= s.sausages.len()) {
+                return Err(serde::de::Error::custom(format!(
+                    "state {i} carries a sausage that does not exist"
+                )));
+            }
+            let state = LevelState {
+                player_pos: s.player_pos,
+                player_dir: s.player_dir,
+                sausages: s.sausages,
+                carried: s.carried,
+                description: Arc::clone(&graph.description),
+            };
+            if !graph.states.fits(&state) {
+                return Err(serde::de::Error::custom(format!(
+                    "state {i} does not fit its level"
+                )));
+            }
+            let (_, is_new) = graph.insert_state(&state);
+            if !is_new {
+                return Err(serde::de::Error::custom(format!(
+                    "state {i} appears more than once"
+                )));
+            }
+        }
+        if graph.state_count() == 0 {
+            return Err(serde::de::Error::custom("graph has no states"));
+        }
+
+        let mut targets: Vec<[Option<StateId>; 4]> = vec![[None; 4]; graph.state_count()];
+        for edge in serialized.edges {
+            for id in [edge.source, edge.target] {
+                if id as usize >= graph.state_count() {
+                    return Err(serde::de::Error::custom(format!("unknown state id {id}")));
+                }
+            }
+            let m = Move::RELATIVE
+                .iter()
+                .position(|m| *m == edge.movement)
+                .ok_or_else(|| {
+                    serde::de::Error::custom(format!(
+                        "edge from state {} is not a relative move",
+                        edge.source
+                    ))
+                })?;
+            targets[edge.source as usize][m] = Some(edge.target);
+        }
+        for (id, targets) in targets.into_iter().enumerate() {
+            graph.successors[id] = match targets {
+                [None, None, None, None] => None,
+                [Some(f), Some(b), Some(l), Some(r)] => Some([f, b, l, r]),
+                _ => {
+                    return Err(serde::de::Error::custom(format!(
+                        "state {id} is missing some of its edges"
+                    )))
+                }
+            };
+        }
+        graph.explored = graph.successors.iter().flatten().count();
+        let successors = &graph.successors;
+        graph
+            .frontier
+            .retain(|id| successors[*id as usize].is_none());
+        graph.unexplored = graph.frontier.len();
+
+        Ok(graph)
+    }
+}
+
+#[wasm_bindgen]
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub enum SausageOrientation {
     Horizontal,
@@ -437,6 +1555,7 @@ impl From<SausageOrientation> for IVec3 {
     }
 }
 
+#[wasm_bindgen]
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Sausage {
     pos: IVec3,
@@ -445,6 +1564,7 @@ pub struct Sausage {
     pub orientation: SausageOrientation,
 }
 
+#[wasm_bindgen]
 impl Sausage {
     pub fn check_collision(&self, x: i32, y: i32, z: i32) -> bool {
         let check_pos = IVec3::new(x, y, z);
@@ -452,7 +1572,7 @@ impl Sausage {
     }
 
     pub fn pos(&self) -> Vec<i32> {
-        vec![self.pos.x, self.pos.y]
+        vec![self.pos.x, self.pos.y, self.pos.z]
     }
 
     pub fn cooked(&self) -> Vec<u8> {
@@ -469,74 +1589,204 @@ impl Sausage {
     }
 }
 
+/// Explores the level described by `level_description`. `budget` is a
+/// `SearchBudget` object, or undefined to explore everything. `on_progress` is
+/// called periodically with a `SearchProgress` object and can cancel the
+/// search by returning `false`, the graph explored so far is then returned.
+/// Invalid levels are rejected with the problems found by
+/// `LevelDescription::validate`.
 #[wasm_bindgen]
-pub fn solve(level_description: JsValue) -> Result<LevelGraph, JsError> {
+pub fn solve(
+    level_description: JsValue,
+    budget: JsValue,
+    on_progress: Option<js_sys::Function>,
+) -> Result<LevelGraph, JsValue> {
     console_error_panic_hook::set_once();
 
-    let parsed: LevelDescription = match serde_wasm_bindgen::from_value(level_description) {
-        Ok(d) => d,
-        Err(e) => {
-            return Err(JsError::new(&format!(
-                "error parsing level description: {e:?}"
-            )));
-        }
+    let parsed = parse_level_description(level_description)?;
+    let budget: SearchBudget = if budget.is_undefined() || budget.is_null() {
+        SearchBudget::default()
+    } else {
+        serde_wasm_bindgen::from_value(budget)
+            .map_err(|e| JsError::new(&format!("error parsing search budget: {e:?}")))?
     };
-    Ok(generate_graph(&parsed))
+
+    let mut callback_error = None;
+    let graph = generate_graph_with(&parsed, &budget, |progress| {
+        let Some(on_progress) = &on_progress else {
+            return ControlFlow::Continue(());
+        };
+        let result = progress
+            .serialize(&js_serializer())
+            .map_err(JsValue::from)
+            .and_then(|progress| on_progress.call1(&JsValue::NULL, &progress));
+        match result {
+            Ok(keep_going) if keep_going == JsValue::FALSE => ControlFlow::Break(()),
+            Ok(_) => ControlFlow::Continue(()),
+            Err(e) => {
+                callback_error = Some(e);
+                ControlFlow::Break(())
+            }
+        }
+    });
+
+    match callback_error {
+        Some(e) => Err(e),
+        None => Ok(graph),
+    }
 }
 
-pub fn generate_graph(level_description: &LevelDescription) -> LevelGraph {
-    let initial_state = Arc::new(LevelState::from(level_description));
-    #[allow(clippy::mutable_key_type)]
-    let mut states: HashSet<Arc<LevelState>> = HashSet::new();
-    states.insert(Arc::clone(&initial_state));
+/// Parses and validates a level description. If the level is invalid the
+/// error is an `Error` listing the problems in its message, with a
+/// `problems` property holding them as serialized `LevelProblem`s.
+fn parse_level_description(level_description: JsValue) -> Result<LevelDescription, JsValue> {
+    let parsed: LevelDescription = serde_wasm_bindgen::from_value(level_description)
+        .map_err(|e| JsError::new(&format!("error parsing level description: {e:?}")))?;
+    let problems = parsed.validate();
+    if problems.is_empty() {
+        return Ok(parsed);
+    }
 
-    let mut exploration_queue: VecDeque<Arc<LevelState>> = VecDeque::new();
-    exploration_queue.push_back(Arc::clone(&initial_state));
+    let message = problems
+        .iter()
+        .map(|problem| problem.to_string())
+        .collect::<Vec<String>>()
+        .join(", ");
+    let error = js_sys::Error::new(&format!("invalid level description: {message}"));
+    let problems = problems
+        .serialize(&js_serializer())
+        .map_err(|e| JsError::new(&format!("error serializing level problems: {e:?}")))?;
+    js_sys::Reflect::set(&error, &JsValue::from_str("problems"), &problems)?;
+    Err(error.into())
+}
 
-    let mut explored: HashSet<Arc<LevelState>> = HashSet::new();
-    let mut edges: Vec<(Arc<LevelState>, Arc<LevelState>)> = Vec::new();
+/// Limits on how much of a level `generate_graph_with` explores, `None`
+/// meaning no limit. Exploration stops once a limit is reached, so the graph
+/// can overshoot the state and edge limits by what the last explored state
+/// added.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchBudget {
+    pub max_states: Option<usize>,
+    pub max_edges: Option<usize>,
+    /// states further than this many moves from the initial state, or from
+    /// the frontier of a graph that is explored further, are added to the
+    /// graph but not explored
+    pub max_depth: Option<u32>,
+}
 
-    while let Some(current_state) = exploration_queue.pop_front() {
-        if explored.contains(&current_state) {
-            continue;
-        }
+/// Why a graph was not explored completely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Incomplete {
+    StateLimit,
+    EdgeLimit,
+    DepthLimit,
+    Cancelled,
+    /// only part of the graph has been asked for, see `LevelGraph::expand`
+    Unexplored,
+}
 
-        match current_state.get_status() {
-            LevelStatus::Lost => continue,
-            LevelStatus::Solution => continue,
-            LevelStatus::Burnt => continue,
-            _ => (),
-        }
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SearchProgress {
+    /// states whose successors have been generated
+    pub explored: usize,
+    /// states waiting to be explored
+    pub queued: usize,
+    pub states: usize,
+    pub edges: usize,
+}
+
+/// How many states are explored between calls to the progress callback.
+const PROGRESS_INTERVAL: usize = 1024;
+
+pub fn generate_graph(level_description: &LevelDescription) -> LevelGraph {
+    generate_graph_with(level_description, &SearchBudget::default(), |_| {
+        ControlFlow::Continue(())
+    })
+}
+
+/// Explores the level breadth first until every reachable state has been
+/// explored or `budget` runs out, see `LevelGraph::explore`.
+pub fn generate_graph_with(
+    level_description: &LevelDescription,
+    budget: &SearchBudget,
+    on_progress: impl FnMut(&SearchProgress) -> ControlFlow<()>,
+) -> LevelGraph {
+    let mut graph = LevelGraph::new(level_description);
+    graph.explore(budget, on_progress);
+    graph
+}
+
+impl LevelGraph {
+    /// Explores the frontier breadth first until it is empty or `budget` runs
+    /// out. The depth limit counts layers from the current frontier, which
+    /// for a new graph is the distance from the initial state. `on_progress`
+    /// is called every `PROGRESS_INTERVAL` explored states and once at the
+    /// end, returning `ControlFlow::Break` cancels the search without a call
+    /// at the end.
+    pub fn explore(
+        &mut self,
+        budget: &SearchBudget,
+        mut on_progress: impl FnMut(&SearchProgress) -> ControlFlow<()>,
+    ) {
+        let mut exploration_queue: VecDeque<(StateId, u32)> =
+            self.frontier.drain(..).map(|id| (id, 0)).collect();
+        let mut deferred = Vec::new();
+        let mut stopped = None;
 
-        let new_state = Arc::new(current_state.get_next_state(current_state.player_dir));
-        let saved_state = states.get_or_insert(new_state);
-        edges.push((Arc::clone(&current_state), Arc::clone(saved_state)));
-        exploration_queue.push_back(saved_state.clone());
+        while let Some((current_id, depth)) = exploration_queue.pop_front() {
+            if self.successors[current_id as usize].is_some() {
+                continue;
+            }
+            if budget.max_depth.is_some_and(|max| depth >= max) {
+                deferred.push(current_id);
+                stopped = Some(Incomplete::DepthLimit);
+                continue;
+            }
+            if budget
+                .max_states
+                .is_some_and(|max| self.state_count() >= max)
+            {
+                exploration_queue.push_front((current_id, depth));
+                stopped = Some(Incomplete::StateLimit);
+                break;
+            }
+            if budget.max_edges.is_some_and(|max| self.explored * 4 >= max) {
+                exploration_queue.push_front((current_id, depth));
+                stopped = Some(Incomplete::EdgeLimit);
+                break;
+            }
 
-        let new_state = Arc::new(current_state.get_next_state(-current_state.player_dir));
-        let saved_state = states.get_or_insert(new_state);
-        edges.push((Arc::clone(&current_state), Arc::clone(saved_state)));
-        exploration_queue.push_back(saved_state.clone());
+            self.explore_state(current_id);
+            exploration_queue.extend(self.frontier.drain(..).map(|id| (id, depth + 1)));
 
-        let new_state =
-            Arc::new(current_state.get_next_state(current_state.player_dir.cross(IVec3::Z)));
-        let saved_state = states.get_or_insert(new_state);
-        edges.push((Arc::clone(&current_state), Arc::clone(saved_state)));
-        exploration_queue.push_back(saved_state.clone());
+            if self.explored.is_multiple_of(PROGRESS_INTERVAL) {
+                let progress = self.progress(exploration_queue.len());
+                if on_progress(&progress).is_break() {
+                    stopped = Some(Incomplete::Cancelled);
+                    break;
+                }
+            }
+        }
 
-        let new_state =
-            Arc::new(current_state.get_next_state(-current_state.player_dir.cross(IVec3::Z)));
-        let saved_state = states.get_or_insert(new_state);
-        edges.push((Arc::clone(&current_state), Arc::clone(saved_state)));
-        exploration_queue.push_back(saved_state.clone());
+        if stopped != Some(Incomplete::Cancelled) {
+            let _ = on_progress(&self.progress(exploration_queue.len()));
+        }
 
-        explored.insert(Arc::clone(&current_state));
+        self.frontier = deferred
+            .into_iter()
+            .chain(exploration_queue.into_iter().map(|(id, _)| id))
+            .collect();
+        self.update_incomplete(stopped.unwrap_or(Incomplete::Unexplored));
     }
 
-    LevelGraph {
-        states,
-        edges,
-        initial_state,
-        level_description: level_description.clone(),
+    fn progress(&self, queued: usize) -> SearchProgress {
+        SearchProgress {
+            explored: self.explored,
+            queued,
+            states: self.state_count(),
+            edges: self.explored * 4,
+        }
     }
 }
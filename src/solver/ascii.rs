@@ -0,0 +1,347 @@
+//! A plain text format for levels and states that is easier to write and
+//! review than JSON. A level is drawn one layer at a time, each layer
+//! starting with a header giving its height and the position of its top left
+//! cell, followed by one line per row going south:
+//!
+//! ```text
+//! z=0 x=2 y=1
+//! ... ... ### === ===
+//! ### ### ### === ===
+//! z=1 x=2 y=1
+//! ... ... n00 ... ...
+//! @>  ... u00 ... ...
+//! ```
+//!
+//! Cells are three characters wide and separated by a space:
+//!
+//! - `...` nothing, which is water on the bottom layer
+//! - `###` ground
+//! - `===` grill
+//! - `HHH` ladder
+//! - `@>`, `@v`, `@<` and `@^` the player facing east, south, west or north
+//! - a sausage half, written as its shape followed by how often its bottom
+//!   and top faces have been cooked. `(` and `)` are the west and east halves
+//!   of a horizontal sausage, `n` and `u` the north and south halves of a
+//!   vertical one. Counts from 10 to 35 are written `a` to `z`, higher ones
+//!   as `+`, which can not be read back.
+//!
+//! Blank lines are ignored. Sausages are listed in reading order of their
+//! first half, lowest layer first, rather than in the order of the level that
+//! was drawn, so reading a drawing back can number them differently. A drawn
+//! state does not show which sausage the fork carries either.
+//!
+//! A level played by other rules than the default ones starts with a line
+//! giving the rules that differ, named as the fields of `Ruleset`:
+//!
+//! ```text
+//! rules return_to_start=false max_cooked=2
+//! ```
+
+use super::*;
+use std::fmt;
+
+/// Draws the level in its initial state.
+pub fn print_level(level_description: &LevelDescription) -> String {
+    render(
+        level_description,
+        level_description.start_pos,
+        level_description.start_dir,
+        &level_description.sausages,
+    )
+}
+
+/// Draws the terrain of the state's level with the player and sausages where
+/// they are in `state`.
+pub fn print_state(state: &LevelState) -> String {
+    render(
+        &state.description,
+        state.player_pos,
+        state.player_dir,
+        &state.sausages,
+    )
+}
+
+fn render(
+    description: &LevelDescription,
+    player_pos: IVec3,
+    player_dir: IVec3,
+    sausages: &[Sausage],
+) -> String {
+    let mut cells: HashMap<IVec3, String> = HashMap::new();
+    for tile in &description.ground {
+        cells.insert(*tile, "###".to_string());
+    }
+    for tile in &description.grills {
+        cells.insert(*tile, "===".to_string());
+    }
+    for tile in &description.ladders {
+        cells.insert(*tile, "HHH".to_string());
+    }
+    for sausage in sausages {
+        let shapes = match sausage.orientation {
+            SausageOrientation::Horizontal => ['(', ')'],
+            SausageOrientation::Vertical => ['n', 'u'],
+        };
+        for (j, (tile, shape)) in [sausage.pos, sausage.pos2()]
+            .into_iter()
+            .zip(shapes)
+            .enumerate()
+        {
+            cells.insert(
+                tile,
+                format!(
+                    "{shape}{}{}",
+                    print_count(sausage.cooked[0][j]),
+                    print_count(sausage.cooked[1][j])
+                ),
+            );
+        }
+    }
+    let facing = match Move::from_direction(player_dir) {
+        Some(Move::North) => '^',
+        Some(Move::South) => 'v',
+        Some(Move::West) => '<',
+        _ => '>',
+    };
+    cells.insert(player_pos, format!("@{facing} "));
+
+    let (min, max) = cells
+        .keys()
+        .fold((IVec3::MAX, IVec3::MIN), |(min, max), tile| {
+            (min.min(*tile), max.max(*tile))
+        });
+
+    let mut out = String::new();
+    let rules = print_rules(&description.rules);
+    if !rules.is_empty() {
+        out.push_str(&format!("rules {rules}\n"));
+    }
+    for z in min.z..=max.z {
+        if !cells.keys().any(|tile| tile.z == z) {
+            continue;
+        }
+        out.push_str(&format!("z={z} x={} y={}\n", min.x, min.y));
+        for y in min.y..=max.y {
+            let row = (min.x..=max.x)
+                .map(|x| {
+                    cells
+                        .get(&IVec3::new(x, y, z))
+                        .map_or("...", String::as_str)
+                })
+                .collect::<Vec<&str>>()
+                .join(" ");
+            out.push_str(row.trim_end());
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// A cook count as a single base 36 digit, or `+` if it needs more.
+fn print_count(count: u8) -> char {
+    char::from_digit(count.into(), 36).unwrap_or('+')
+}
+
+/// The rules that differ from the default ones, as `name=value` pairs.
+fn print_rules(rules: &Ruleset) -> String {
+    let default = Ruleset::default();
+    let mut pairs = Vec::new();
+    if rules.return_to_start != default.return_to_start {
+        pairs.push(format!("return_to_start={}", rules.return_to_start));
+    }
+    if rules.max_cooked != default.max_cooked {
+        pairs.push(format!("max_cooked={}", rules.max_cooked));
+    }
+    if rules.grill_bounce != default.grill_bounce {
+        pairs.push(format!("grill_bounce={}", rules.grill_bounce));
+    }
+    if rules.sausages_fall != default.sausages_fall {
+        pairs.push(format!("sausages_fall={}", rules.sausages_fall));
+    }
+    pairs.join(" ")
+}
+
+/// Why a level could not be parsed, `line` counting from one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Reads a level in the format described in the module documentation. The
+/// level is not validated, see `LevelDescription::validate`.
+pub fn parse_level(text: &str) -> Result<LevelDescription, ParseError> {
+    let mut ground = HashSet::new();
+    let mut grills = HashSet::new();
+    let mut ladders = HashSet::new();
+    let mut player = None;
+    // sausage halves by tile, with the line they were found on
+    let mut halves: Vec<(IVec3, char, [u8; 2], usize)> = Vec::new();
+
+    let mut rules = Ruleset::default();
+    let mut origin: Option<IVec3> = None;
+    let mut row = 0;
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let error = |message: String| ParseError {
+            line: line_number,
+            message,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(pairs) = line.strip_prefix("rules") {
+            rules = parse_rules(pairs).map_err(error)?;
+            continue;
+        }
+        if line.starts_with("z=") {
+            origin = Some(parse_header(line).ok_or_else(|| {
+                error(format!(
+                    "expected a header like `z=0 x=0 y=0`, found `{line}`"
+                ))
+            })?);
+            row = 0;
+            continue;
+        }
+        let Some(origin) = origin else {
+            return Err(error(
+                "expected a layer header before the first row".to_string(),
+            ));
+        };
+
+        let chars: Vec<char> = line.trim_end().chars().collect();
+        for (column, cell) in chars.chunks(4).enumerate() {
+            let tile = origin + IVec3::new(column as i32, row, 0);
+            let cell: String = cell.iter().take(3).collect();
+            match format!("{cell:<3}").as_str() {
+                "..." => (),
+                "###" => {
+                    ground.insert(tile);
+                }
+                "===" => {
+                    grills.insert(tile);
+                }
+                "HHH" => {
+                    ladders.insert(tile);
+                }
+                player_cell @ ("@> " | "@v " | "@< " | "@^ ") => {
+                    if player.is_some() {
+                        return Err(error(format!("second player at {tile}")));
+                    }
+                    let dir = match player_cell {
+                        "@> " => IVec3::X,
+                        "@v " => IVec3::Y,
+                        "@< " => IVec3::NEG_X,
+                        _ => IVec3::NEG_Y,
+                    };
+                    player = Some((tile, dir));
+                }
+                sausage_cell => {
+                    let mut chars = sausage_cell.chars();
+                    let shape = chars.next().filter(|c| "()nu".contains(*c));
+                    let cooked: Option<Vec<u8>> =
+                        chars.map(|c| c.to_digit(36).map(|d| d as u8)).collect();
+                    match (shape, cooked.as_deref()) {
+                        (Some(shape), Some(&[bottom, top])) => {
+                            halves.push((tile, shape, [bottom, top], line_number))
+                        }
+                        _ => return Err(error(format!("unknown cell `{sausage_cell}` at {tile}"))),
+                    }
+                }
+            }
+        }
+        row += 1;
+    }
+
+    let mut sausages = Vec::new();
+    for (tile, shape, cooked, line) in &halves {
+        let (orientation, other_shape) = match shape {
+            '(' => (SausageOrientation::Horizontal, ')'),
+            'n' => (SausageOrientation::Vertical, 'u'),
+            _ => continue,
+        };
+        let other_tile = *tile + IVec3::from(orientation);
+        let Some((_, _, other_cooked, _)) = halves
+            .iter()
+            .find(|(t, s, _, _)| *t == other_tile && *s == other_shape)
+        else {
+            return Err(ParseError {
+                line: *line,
+                message: format!("sausage half at {tile} has no `{other_shape}` half after it"),
+            });
+        };
+        sausages.push(Sausage {
+            pos: *tile,
+            cooked: [[cooked[0], other_cooked[0]], [cooked[1], other_cooked[1]]],
+            orientation,
+        });
+    }
+    for (tile, shape, _, line) in &halves {
+        let first_tile = match shape {
+            ')' => *tile - IVec3::X,
+            'u' => *tile - IVec3::Y,
+            _ => continue,
+        };
+        if !sausages.iter().any(|s| s.pos == first_tile) {
+            return Err(ParseError {
+                line: *line,
+                message: format!("sausage half at {tile} has no half before it"),
+            });
+        }
+    }
+
+    let Some((start_pos, start_dir)) = player else {
+        return Err(ParseError {
+            line: text.lines().count(),
+            message: "the level has no player".to_string(),
+        });
+    };
+
+    Ok(LevelDescription {
+        start_pos,
+        start_dir,
+        ground,
+        grills,
+        ladders,
+        sausages,
+        rules,
+    })
+}
+
+/// Reads the `name=value` pairs of a rules line into the rules they change
+/// from the default ones.
+fn parse_rules(pairs: &str) -> Result<Ruleset, String> {
+    let mut rules = Ruleset::default();
+    for pair in pairs.split_whitespace() {
+        let invalid = || format!("expected a rule like `max_cooked=2`, found `{pair}`");
+        let (name, value) = pair.split_once('=').ok_or_else(invalid)?;
+        match name {
+            "return_to_start" => rules.return_to_start = value.parse().map_err(|_| invalid())?,
+            "max_cooked" => rules.max_cooked = value.parse().map_err(|_| invalid())?,
+            "grill_bounce" => rules.grill_bounce = value.parse().map_err(|_| invalid())?,
+            "sausages_fall" => rules.sausages_fall = value.parse().map_err(|_| invalid())?,
+            _ => return Err(format!("unknown rule `{name}`")),
+        }
+    }
+    Ok(rules)
+}
+
+/// Reads a header like `z=1 x=-2 y=0` into the position of the top left cell
+/// of the layer.
+fn parse_header(line: &str) -> Option<IVec3> {
+    let mut coords = [None; 3];
+    for part in line.split_whitespace() {
+        let (axis, value) = part.split_once('=')?;
+        let index = ["x", "y", "z"].iter().position(|a| *a == axis)?;
+        coords[index] = Some(value.parse().ok()?);
+    }
+    Some(IVec3::new(coords[0]?, coords[1]?, coords[2]?))
+}
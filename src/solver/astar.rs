@@ -0,0 +1,169 @@
+//! Best first search for a shortest solution, for levels whose graph is too
+//! large to build. Only the states that can still lead to a solution as fast
+//! as the best one found so far are explored.
+
+use super::*;
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+/// A lower bound on the number of moves needed to solve the level from
+/// `state`, or `u32::MAX` if it can not be solved. A move either turns the
+/// player or moves the player one tile and every sausage half a few tiles
+/// horizontally: one for a pushed half, but swinging a lifted sausage around
+/// carries its far half four tiles and drags what is in the way up to three.
+/// So the player's distance to the start when the rules have them return
+/// there and each uncooked sausage half's distance to the nearest grill,
+/// divided by four wherever a sausage can be lifted, are all lower bounds,
+/// plus a move to roll over for halves with both faces uncooked.
+pub fn heuristic(state: &LevelState) -> u32 {
+    let description = &state.description;
+
+    let offset = state.player_pos - description.start_pos;
+    let player = match description.rules.return_to_start {
+        true => ((offset.x.abs() + offset.y.abs()) as u32)
+            .max((state.player_dir != description.start_dir) as u32),
+        false => 0,
+    };
+
+    // the fork only lifts sausages from above, which needs the player to be
+    // up high or to be able to climb there
+    let reach = match state.carried.is_some()
+        || state.player_pos.z > 1
+        || !description.ladders.is_empty()
+    {
+        true => 4,
+        false => 1,
+    };
+    let sausages = state
+        .sausages
+        .iter()
+        .flat_map(|s| {
+            [s.pos, s.pos2()]
+                .into_iter()
+                .enumerate()
+                .map(move |(j, half)| {
+                    (
+                        half,
+                        (s.cooked[0][j] == 0) as u32 + (s.cooked[1][j] == 0) as u32,
+                    )
+                })
+                .filter(|(_, uncooked)| *uncooked > 0)
+        })
+        .map(|(half, uncooked)| {
+            // even a half above a grill needs a move to cook
+            description
+                .grills
+                .iter()
+                .map(|grill| (grill.x - half.x).unsigned_abs() + (grill.y - half.y).unsigned_abs())
+                .min()
+                .map_or(u32::MAX, |distance| {
+                    distance.div_ceil(reach).max(1) + uncooked - 1
+                })
+        })
+        .max()
+        .unwrap_or(0);
+
+    player.max(sausages)
+}
+
+/// Finds a shortest solution with A* search guided by `heuristic`. Only the
+/// limits of `budget` are used, the depth limit bounding the length of the
+/// solution. Also returns how much of the level had to be explored.
+pub fn astar_solution(
+    level_description: &LevelDescription,
+    budget: &SearchBudget,
+) -> (ShortestSolution, SearchProgress) {
+    astar_solution_from(
+        LevelState::initial(Arc::new(level_description.clone())),
+        budget,
+    )
+}
+
+/// Like `astar_solution` but starting from any state of a level. The
+/// solution then starts with `initial` rather than the initial state of the
+/// level.
+pub(crate) fn astar_solution_from(
+    initial: LevelState,
+    budget: &SearchBudget,
+) -> (ShortestSolution, SearchProgress) {
+    let description = Arc::clone(&initial.description);
+    let mut states = packed::StateStore::new(packed::StateLayout::new(&description));
+    // the fewest moves known to reach each state and the move that did so
+    let mut costs: Vec<u32> = Vec::new();
+    let mut parents: Vec<Option<(StateId, Move)>> = Vec::new();
+    // ordered by estimated total cost, preferring states further along
+    let mut open: BinaryHeap<Reverse<(u32, Reverse<u32>, StateId)>> = BinaryHeap::new();
+
+    states.insert(&initial);
+    costs.push(0);
+    parents.push(None);
+    let estimate = heuristic(&initial);
+    if estimate != u32::MAX {
+        open.push(Reverse((estimate, Reverse(0), 0)));
+    }
+
+    let mut explored = 0;
+    let mut limited = false;
+    let progress =
+        |explored: usize, open: &BinaryHeap<_>, states: &packed::StateStore| SearchProgress {
+            explored,
+            queued: open.len(),
+            states: states.len(),
+            edges: explored * 4,
+        };
+
+    while let Some(Reverse((_, Reverse(cost), current_id))) = open.pop() {
+        if cost > costs[current_id as usize] {
+            continue;
+        }
+        let current_state = states.get(current_id, &description);
+        match current_state.get_status() {
+            LevelStatus::Solution => {
+                let solution =
+                    trace_solution(&parents, current_id, |id| states.get(id, &description));
+                return (
+                    ShortestSolution::Solved(solution),
+                    progress(explored, &open, &states),
+                );
+            }
+            LevelStatus::Lost | LevelStatus::Burnt | LevelStatus::Drowned => continue,
+            LevelStatus::Unsolved => (),
+        }
+
+        if budget.max_depth.is_some_and(|max| cost >= max) {
+            limited = true;
+            continue;
+        }
+        if budget.max_states.is_some_and(|max| states.len() >= max)
+            || budget.max_edges.is_some_and(|max| explored * 4 >= max)
+        {
+            limited = true;
+            break;
+        }
+
+        explored += 1;
+        for m in Move::RELATIVE {
+            let next_state = current_state.get_next_state(m);
+            let (id, is_new) = states.insert(&next_state);
+            if is_new {
+                costs.push(u32::MAX);
+                parents.push(None);
+            }
+            if cost + 1 >= costs[id as usize] {
+                continue;
+            }
+            let estimate = heuristic(&next_state);
+            if estimate == u32::MAX {
+                continue;
+            }
+            costs[id as usize] = cost + 1;
+            parents[id as usize] = Some((current_id, m));
+            open.push(Reverse((cost + 1 + estimate, Reverse(cost + 1), id)));
+        }
+    }
+
+    let result = match limited {
+        true => ShortestSolution::Inconclusive,
+        false => ShortestSolution::Unsolvable,
+    };
+    (result, progress(explored, &open, &states))
+}
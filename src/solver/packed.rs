@@ -0,0 +1,303 @@
+//! Compact storage for the states of a level graph. Every state of a level is
+//! packed into the same number of 64 bit words and all of them are kept in a
+//! single flat buffer, the level description is only held once by the graph.
+
+use super::*;
+use std::collections::hash_map::Entry;
+
+const DIRECTIONS: [IVec3; 4] = [IVec3::X, IVec3::Y, IVec3::NEG_X, IVec3::NEG_Y];
+const DIR_BITS: u32 = 2;
+const ORIENTATION_BITS: u32 = 1;
+
+/// Bit layout of the states of one level. Positions are stored relative to
+/// the corner of a box around the level that is large enough to hold every
+/// position a sausage can reach before the state is discarded.
+#[derive(Debug, Clone)]
+pub(crate) struct StateLayout {
+    min: IVec3,
+    coord_bits: [u32; 3],
+    sausage_count: usize,
+    /// bits for the carried sausage, stored as its index plus one
+    carried_bits: u32,
+    /// cook counts are saturated to the first count the rules call burnt,
+    /// which does not merge states that behave differently
+    burnt: u8,
+    cook_bits: u32,
+    words: usize,
+}
+
+impl StateLayout {
+    pub(crate) fn new(description: &LevelDescription) -> StateLayout {
+        let (min, max) = description
+            .ground
+            .iter()
+            .chain(&description.grills)
+            .chain(&description.ladders)
+            .copied()
+            .chain([description.start_pos])
+            .chain(description.sausages.iter().flat_map(|s| [s.pos, s.pos2()]))
+            .fold((IVec3::MAX, IVec3::MIN), |(min, max), tile| {
+                (min.min(tile), max.max(tile))
+            });
+
+        // sausages can hang over the edge of the level and can be pushed
+        // further out while they fall, all the way down to z=0 however high
+        // the level starts, and stack on top of each other
+        let min = min.with_z(min.z.min(1));
+        let sausage_count = description.sausages.len();
+        let height = max.z - min.z;
+        let min = min - IVec3::new(2 + height, 2 + height, 1);
+        let max = max + IVec3::new(2 + height, 2 + height, 1 + sausage_count as i32);
+        let coord_bits = (max - min)
+            .to_array()
+            .map(|extent| u32::BITS - (extent as u32).leading_zeros());
+
+        let burnt = description.rules.max_cooked.saturating_add(1);
+        let cook_bits = u8::BITS - burnt.leading_zeros();
+        let vec_bits: u32 = coord_bits.iter().sum();
+        let sausage_bits = vec_bits + ORIENTATION_BITS + 4 * cook_bits;
+        let carried_bits = u32::BITS - (sausage_count as u32).leading_zeros();
+        let bits = vec_bits as usize
+            + DIR_BITS as usize
+            + carried_bits as usize
+            + sausage_count * sausage_bits as usize;
+
+        StateLayout {
+            min,
+            coord_bits,
+            sausage_count,
+            carried_bits,
+            burnt,
+            cook_bits,
+            words: bits.div_ceil(64).max(1),
+        }
+    }
+
+    /// Packs `state`, or returns `None` if it can not be represented with
+    /// this layout.
+    fn pack(&self, state: &LevelState) -> Option<Vec<u64>> {
+        if state.sausages.len() != self.sausage_count
+            || state.carried.is_some_and(|i| i >= self.sausage_count)
+        {
+            return None;
+        }
+        let mut words = vec![0; self.words];
+        let mut writer = BitWriter {
+            words: &mut words,
+            bit: 0,
+        };
+        self.write_vec(&mut writer, state.player_pos)?;
+        let dir = DIRECTIONS.iter().position(|d| *d == state.player_dir)?;
+        writer.write(dir as u64, DIR_BITS);
+        writer.write(state.carried.map_or(0, |i| i as u64 + 1), self.carried_bits);
+        for sausage in &state.sausages {
+            self.write_vec(&mut writer, sausage.pos)?;
+            writer.write(
+                match sausage.orientation {
+                    SausageOrientation::Horizontal => 0,
+                    SausageOrientation::Vertical => 1,
+                },
+                ORIENTATION_BITS,
+            );
+            for cooked in sausage.cooked.as_flattened() {
+                writer.write((*cooked).min(self.burnt) as u64, self.cook_bits);
+            }
+        }
+        Some(words)
+    }
+
+    fn unpack(&self, words: &[u64], description: &Arc<LevelDescription>) -> LevelState {
+        let mut reader = BitReader { words, bit: 0 };
+        let player_pos = self.read_vec(&mut reader);
+        let player_dir = DIRECTIONS[reader.read(DIR_BITS) as usize];
+        let carried = (reader.read(self.carried_bits) as usize).checked_sub(1);
+        let sausages = (0..self.sausage_count)
+            .map(|_| {
+                let pos = self.read_vec(&mut reader);
+                let orientation = match reader.read(ORIENTATION_BITS) {
+                    0 => SausageOrientation::Horizontal,
+                    _ => SausageOrientation::Vertical,
+                };
+                let mut cooked = [[0; 2]; 2];
+                for c in cooked.as_flattened_mut() {
+                    *c = reader.read(self.cook_bits) as u8;
+                }
+                Sausage {
+                    pos,
+                    cooked,
+                    orientation,
+                }
+            })
+            .collect();
+        LevelState {
+            player_pos,
+            player_dir,
+            sausages,
+            carried,
+            description: Arc::clone(description),
+        }
+    }
+
+    fn write_vec(&self, writer: &mut BitWriter, v: IVec3) -> Option<()> {
+        for ((c, min), bits) in v
+            .to_array()
+            .iter()
+            .zip(self.min.to_array())
+            .zip(self.coord_bits)
+        {
+            let value = u64::try_from(c - min).ok().filter(|v| *v < 1 << bits)?;
+            writer.write(value, bits);
+        }
+        Some(())
+    }
+
+    fn read_vec(&self, reader: &mut BitReader) -> IVec3 {
+        let mut v = self.min.to_array();
+        for (c, bits) in v.iter_mut().zip(self.coord_bits) {
+            *c += reader.read(bits) as i32;
+        }
+        IVec3::from_array(v)
+    }
+}
+
+struct BitWriter<'a> {
+    words: &'a mut [u64],
+    bit: usize,
+}
+
+impl BitWriter<'_> {
+    fn write(&mut self, value: u64, bits: u32) {
+        if bits == 0 {
+            return;
+        }
+        let (word, offset) = (self.bit / 64, (self.bit % 64) as u32);
+        self.words[word] |= value << offset;
+        if offset + bits > 64 {
+            self.words[word + 1] |= value >> (64 - offset);
+        }
+        self.bit += bits as usize;
+    }
+}
+
+struct BitReader<'a> {
+    words: &'a [u64],
+    bit: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, bits: u32) -> u64 {
+        if bits == 0 {
+            return 0;
+        }
+        let (word, offset) = (self.bit / 64, (self.bit % 64) as u32);
+        let mut value = self.words[word] >> offset;
+        if offset + bits > 64 {
+            value |= self.words[word + 1] << (64 - offset);
+        }
+        self.bit += bits as usize;
+        value & (u64::MAX >> (64 - bits))
+    }
+}
+
+/// Every distinct state of a level, packed with a `StateLayout` and numbered
+/// in insertion order.
+#[derive(Debug)]
+pub(crate) struct StateStore {
+    layout: StateLayout,
+    /// the packed states back to back, `layout.words` words each
+    words: Vec<u64>,
+    /// hash of the packed words to the first state inserted with that hash
+    index: HashMap<u64, StateId>,
+    /// any further states sharing a hash with one in `index`
+    collisions: HashMap<u64, Vec<StateId>>,
+}
+
+impl StateStore {
+    pub(crate) fn new(layout: StateLayout) -> StateStore {
+        StateStore {
+            layout,
+            words: Vec::new(),
+            index: HashMap::new(),
+            collisions: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.words.len() / self.layout.words
+    }
+
+    fn packed(&self, id: StateId) -> &[u64] {
+        let start = id as usize * self.layout.words;
+        &self.words[start..start + self.layout.words]
+    }
+
+    pub(crate) fn get(&self, id: StateId, description: &Arc<LevelDescription>) -> LevelState {
+        self.layout.unpack(self.packed(id), description)
+    }
+
+    pub(crate) fn find(&self, state: &LevelState) -> Option<StateId> {
+        let packed = self.layout.pack(state)?;
+        self.find_packed(&packed, hash(&packed))
+    }
+
+    fn find_packed(&self, packed: &[u64], hash: u64) -> Option<StateId> {
+        let id = *self.index.get(&hash)?;
+        if self.packed(id) == packed {
+            return Some(id);
+        }
+        self.collisions
+            .get(&hash)?
+            .iter()
+            .copied()
+            .find(|id| self.packed(*id) == packed)
+    }
+
+    /// Whether `state` can be stored, which it can unless it lies outside
+    /// the bounds of the level or does not have the level's sausages.
+    pub(crate) fn fits(&self, state: &LevelState) -> bool {
+        self.layout.pack(state).is_some()
+    }
+
+    /// Adds `state` unless it is already stored. Returns the id of the state
+    /// and whether it was newly added.
+    pub(crate) fn insert(&mut self, state: &LevelState) -> (StateId, bool) {
+        let packed = self
+            .layout
+            .pack(state)
+            .expect("state lies outside the bounds of its level");
+        let hash = hash(&packed);
+        if let Some(id) = self.find_packed(&packed, hash) {
+            return (id, false);
+        }
+
+        let id = self.len() as StateId;
+        self.words.extend_from_slice(&packed);
+        match self.index.entry(hash) {
+            Entry::Occupied(_) => self.collisions.entry(hash).or_default().push(id),
+            Entry::Vacant(entry) => {
+                entry.insert(id);
+            }
+        }
+        (id, true)
+    }
+
+    /// Approximate number of bytes allocated on the heap by the store.
+    pub(crate) fn heap_size(&self) -> usize {
+        let entry = std::mem::size_of::<(u64, StateId)>() + 1;
+        self.words.capacity() * std::mem::size_of::<u64>()
+            + self.index.capacity() * entry
+            + self
+                .collisions
+                .values()
+                .map(|ids| entry + ids.capacity() * std::mem::size_of::<StateId>())
+                .sum::<usize>()
+    }
+}
+
+fn hash(packed: &[u64]) -> u64 {
+    packed.iter().fold(0, |hash, word| {
+        (hash ^ word)
+            .wrapping_mul(0x9e37_79b9_7f4a_7c15)
+            .rotate_left(29)
+    })
+}
@@ -0,0 +1,138 @@
+//! Works out what a push moves before anything is moved. Starting from the
+//! tile that is pushed, every sausage in the way is added to the push along
+//! with the sausage that runs into it, then the sausages resting on the ones
+//! that move are carried along if they have room. The push is then applied
+//! as a whole or, if anything in the way can not move, not at all, so no
+//! sausage moves twice and the order the tiles are visited in does not
+//! change the outcome.
+
+use super::*;
+
+/// Why a sausage moves in a push.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Cause {
+    /// it is on the tile the push starts from
+    Start,
+    /// the sausage at this index of `Push::moved` runs into it
+    Pushed(usize),
+    /// it rests on the sausage at this index of `Push::moved`
+    Carried(usize),
+}
+
+/// A sausage moved by a push. Pushed sausages roll when pushed across and
+/// slide when pushed along their length, carried ones always slide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Moved {
+    pub(crate) sausage: usize,
+    pub(crate) rolls: bool,
+    pub(crate) cause: Cause,
+}
+
+/// Every sausage a push moves one tile towards `dir`, each listed after the
+/// sausage that moves it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Push {
+    pub(crate) dir: IVec3,
+    pub(crate) moved: Vec<Moved>,
+}
+
+impl Push {
+    /// What pushing whatever is at `tile` towards `dir` moves, or `None` if
+    /// the tile is terrain or something in the way can not move. Sausages
+    /// resting on a moving one that have no room to come along stay behind
+    /// without holding the push up, as does the one on the fork.
+    pub(crate) fn resolve(state: &LevelState, tile: IVec3, dir: IVec3) -> Option<Push> {
+        let mut push = Push {
+            dir,
+            moved: Vec::new(),
+        };
+        if !push.make_room(state, tile, Cause::Start) {
+            return None;
+        }
+        let mut i = 0;
+        while i < push.moved.len() {
+            let carrier = state.sausages[push.moved[i].sausage];
+            for (rider, sausage) in state.sausages.iter().enumerate() {
+                let resting = [sausage.pos, sausage.pos2()].into_iter().any(|half| {
+                    half + IVec3::NEG_Z == carrier.pos || half + IVec3::NEG_Z == carrier.pos2()
+                });
+                if !resting || push.moves(rider) || state.carried == Some(rider) {
+                    continue;
+                }
+                if push.ahead(sausage).any(|tile| tile == state.player_pos) {
+                    continue;
+                }
+                let len = push.moved.len();
+                push.moved.push(Moved {
+                    sausage: rider,
+                    rolls: false,
+                    cause: Cause::Carried(i),
+                });
+                if !push.clear_ahead(state, len) {
+                    push.moved.truncate(len);
+                }
+            }
+            i += 1;
+        }
+        Some(push)
+    }
+
+    /// Moves the sausages of the push.
+    pub(crate) fn apply(&self, sausages: &mut [Sausage]) {
+        for moved in &self.moved {
+            let sausage = &mut sausages[moved.sausage];
+            sausage.pos += self.dir;
+            if moved.rolls {
+                sausage.cooked.swap(0, 1);
+            }
+        }
+    }
+
+    fn moves(&self, sausage: usize) -> bool {
+        self.moved.iter().any(|moved| moved.sausage == sausage)
+    }
+
+    /// The tiles `sausage` moves into that it does not already cover.
+    fn ahead(&self, sausage: &Sausage) -> impl Iterator<Item = IVec3> {
+        let halves = [sausage.pos, sausage.pos2()];
+        let dir = self.dir;
+        halves
+            .into_iter()
+            .map(move |half| half + dir)
+            .filter(move |tile| !halves.contains(tile))
+    }
+
+    /// Adds the sausage at `tile` and everything in its way to the push.
+    /// Returns false if the tile is terrain or something can not move.
+    fn make_room(&mut self, state: &LevelState, tile: IVec3, cause: Cause) -> bool {
+        if state.description.get_tile_type(tile) != TileType::Water {
+            return false;
+        }
+        let Some(sausage) = state
+            .sausages
+            .iter()
+            .position(|s| s.pos == tile || s.pos2() == tile)
+        else {
+            return true;
+        };
+        if self.moves(sausage) {
+            return true;
+        }
+        let orientation = IVec3::from(state.sausages[sausage].orientation);
+        self.moved.push(Moved {
+            sausage,
+            rolls: self.dir.dot(orientation) == 0,
+            cause,
+        });
+        self.clear_ahead(state, self.moved.len() - 1)
+    }
+
+    /// Makes room for `self.moved[i]` to move into.
+    fn clear_ahead(&mut self, state: &LevelState, i: usize) -> bool {
+        let sausage = state.sausages[self.moved[i].sausage];
+        let ahead: Vec<IVec3> = self.ahead(&sausage).collect();
+        ahead
+            .into_iter()
+            .all(|tile| self.make_room(state, tile, Cause::Pushed(i)))
+    }
+}
@@ -1,44 +1,1201 @@
-use serde_json::value::Value;
-
 use super::*;
 use std::{cell::OnceCell, str::FromStr};
 
+// presets from the level editor
+const SIMPLEST_POSSIBLE_LEVEL: &str = r#"{"start_pos":[2,2,1],"start_dir":[1,0,0],"ground":[[2,2,0],[3,2,0],[4,2,0],[4,1,0]],"grills":[[5,1,0],[5,2,0],[6,2,0],[6,1,0]],"sausages":[{"pos":[4,1,1],"cooked":[[0,0],[0,0]],"orientation":"Vertical"}]}"#;
+const HAPPY_POOL: &str = r#"{"start_pos":[1,5,1],"start_dir":[0,1,0],"ground":[[2,1,0],[2,2,0],[1,2,0],[1,3,0],[1,4,0],[1,5,0],[3,1,0],[4,1,0],[5,1,0],[1,6,0],[2,6,0],[3,6,0],[4,6,0],[5,6,0],[5,5,0],[6,5,0],[6,4,0],[6,3,0],[6,2,0],[6,1,0]],"grills":[[4,3,0],[3,4,0]],"sausages":[{"pos":[3,1,1],"cooked":[[0,0],[0,0]],"orientation":"Horizontal"}]}"#;
+
+/// A case of `test-cases.json`. Levels are written either as a level
+/// description or as the lines of an ASCII drawing, see `ascii`. The moves
+/// are `steps`, preceded by `input_dir` or `move` if given, and `expected` and
+/// `status` are checked against the state after the last of them.
+#[derive(Deserialize)]
+struct TestCase {
+    title: String,
+    start: TestLevel,
+    input_dir: Option<String>,
+    #[serde(rename = "move")]
+    movement: Option<Move>,
+    #[serde(default)]
+    steps: Vec<TestStep>,
+    expected: Option<TestLevel>,
+    status: Option<LevelStatus>,
+}
+
+/// One move of a test case and what to check after it. `no_op` means the move
+/// should not change the state at all, `carrying` whether the fork should
+/// hold a sausage up afterwards.
+#[derive(Deserialize)]
+struct TestStep {
+    input_dir: Option<String>,
+    #[serde(rename = "move")]
+    movement: Option<Move>,
+    expected: Option<TestLevel>,
+    status: Option<LevelStatus>,
+    #[serde(default)]
+    no_op: bool,
+    carrying: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TestLevel {
+    Description(LevelDescription),
+    Ascii(Vec<String>),
+}
+
+impl TestLevel {
+    fn to_state(&self) -> Result<LevelState, String> {
+        match self {
+            TestLevel::Description(description) => Ok(LevelState::from(description)),
+            TestLevel::Ascii(lines) => ascii::parse_level(&lines.join("\n"))
+                .map(|description| LevelState::from(&description))
+                .map_err(|e| format!("error parsing ASCII level: {e}")),
+        }
+    }
+}
+
+fn parse_test_move(input_dir: &Option<String>, movement: Option<Move>) -> Result<Move, String> {
+    match (input_dir.as_deref(), movement) {
+        (None, Some(m)) => Ok(m),
+        (Some("right"), None) => Ok(Move::East),
+        (Some("left"), None) => Ok(Move::West),
+        (Some("down"), None) => Ok(Move::South),
+        (Some("up"), None) => Ok(Move::North),
+        (Some(_), Some(_)) => Err("a step has both `input_dir` and `move`".to_string()),
+        (Some(input_dir), None) => Err(format!("unexpected `input_dir` value {input_dir}")),
+        (None, None) => Err("a step has no `input_dir` or `move`".to_string()),
+    }
+}
+
+/// Describes a state both ways, as JSON to paste into test cases and drawn
+/// to make the difference easy to spot.
+fn describe_state(state: &LevelState) -> String {
+    format!(
+        "{}\n{}",
+        serde_json::to_string(state).unwrap(),
+        ascii::print_state(state)
+    )
+}
+
+/// Runs a test case, returning a description of the first step that did not
+/// go as expected.
+fn run_test_case(test_case: &TestCase) -> Result<(), String> {
+    let mut steps = Vec::new();
+    if test_case.input_dir.is_some() || test_case.movement.is_some() {
+        steps.push((
+            parse_test_move(&test_case.input_dir, test_case.movement)?,
+            None,
+            None,
+            false,
+            None,
+        ));
+    }
+    for step in &test_case.steps {
+        steps.push((
+            parse_test_move(&step.input_dir, step.movement)?,
+            step.expected.as_ref(),
+            step.status,
+            step.no_op,
+            step.carrying,
+        ));
+    }
+    if let Some(last) = steps.last_mut() {
+        last.1 = last.1.or(test_case.expected.as_ref());
+        last.2 = last.2.or(test_case.status);
+    }
+
+    let mut state = test_case.start.to_state()?;
+    let step_count = steps.len();
+    for (i, (m, expected, status, no_op, carrying)) in steps.into_iter().enumerate() {
+        let produced = state.get_next_state(m);
+        let problem = if no_op && produced != state {
+            Some("expected the move to change nothing".to_string())
+        } else if let Some(level) = expected {
+            let expected = level.to_state()?;
+            let matches = match level {
+                TestLevel::Description(_) => produced == expected,
+                // ASCII drawings can not tell the sausages apart
+                TestLevel::Ascii(_) => {
+                    let sorted = |state: &LevelState| {
+                        let mut sausages = state.sausages.clone();
+                        sausages.sort_by_key(|s| s.pos.to_array());
+                        sausages
+                    };
+                    produced.player_pos == expected.player_pos
+                        && produced.player_dir == expected.player_dir
+                        && sorted(&produced) == sorted(&expected)
+                }
+            };
+            (!matches).then(|| format!("Expected: {}", describe_state(&expected)))
+        } else {
+            None
+        }
+        .or_else(|| {
+            status
+                .filter(|status| *status != produced.get_status())
+                .map(|status| {
+                    format!(
+                        "expected status {status:?} but found {:?}",
+                        produced.get_status()
+                    )
+                })
+        })
+        .or_else(|| {
+            carrying
+                .filter(|carrying| *carrying != produced.carried.is_some())
+                .map(|carrying| match carrying {
+                    true => "expected the fork to carry a sausage".to_string(),
+                    false => "expected the fork to carry nothing".to_string(),
+                })
+        });
+        if let Some(problem) = problem {
+            return Err(format!(
+                "step {} of {step_count} ({m:?}) went wrong\nInput:    {}\n{problem}\nProduced: {}",
+                i + 1,
+                describe_state(&state),
+                describe_state(&produced)
+            ));
+        }
+        state = produced;
+    }
+    Ok(())
+}
+
 #[test]
 fn tests() {
-    if let Value::Array(test_cases) =
-        serde_json::from_str(include_str!("./test-cases.json")).unwrap()
-    {
-        for (i, test_case) in test_cases.iter().enumerate() {
-            let title = test_case.get("title").unwrap().as_str().unwrap();
-            let start = LevelState::from(
-                &serde_json::from_value::<LevelDescription>(
-                    test_case.get("start").unwrap().clone(),
+    let test_cases: Vec<TestCase> =
+        serde_json::from_str(include_str!("./test-cases.json")).unwrap();
+    let failures: Vec<String> = test_cases
+        .iter()
+        .enumerate()
+        .filter_map(|(i, test_case)| {
+            run_test_case(test_case).err().map(|e| {
+                format!(
+                    "while running test number {} titled {}\n{e}",
+                    i, test_case.title
                 )
-                .unwrap(),
-            );
-            let expected = LevelState::from(
-                &serde_json::from_value::<LevelDescription>(
-                    test_case.get("expected").unwrap().clone(),
-                )
-                .unwrap(),
-            );
-            let input_dir = match test_case.get("input_dir").unwrap().as_str().unwrap() {
-                "right" => IVec3::X,
-                "left" => -IVec3::X,
-                "down" => IVec3::Y,
-                "up" => -IVec3::Y,
-                _ => panic!("unexpected `input_dir` value found"),
-            };
-            let produced = start.get_next_state(input_dir);
-            assert!(
-                expected == produced,
-                "while running test number {} titled {}\nInput:    {}\nExpected: {}\nProduced: {}",
-                i,
-                title,
-                serde_json::to_string(&start).unwrap(),
-                serde_json::to_string(&expected).unwrap(),
-                serde_json::to_string(&produced).unwrap()
-            );
+            })
+        })
+        .collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}
+
+#[test]
+fn shortest_solution() {
+    let description: LevelDescription = serde_json::from_str(SIMPLEST_POSSIBLE_LEVEL).unwrap();
+    let graph = generate_graph(&description);
+    let solution = match graph.shortest_solution() {
+        ShortestSolution::Solved(solution) => solution,
+        ShortestSolution::Unsolvable | ShortestSolution::Inconclusive => {
+            panic!("level should be solvable")
+        }
+    };
+    assert_eq!(solution.states.len(), solution.moves.len() + 1);
+    assert!(solution.states[0] == graph.initial_state());
+    assert_eq!(
+        solution.states.last().unwrap().get_status(),
+        LevelStatus::Solution
+    );
+    for (i, m) in solution.moves.iter().enumerate() {
+        let state = &solution.states[i];
+        assert!(state.step(*m) == solution.states[i + 1]);
+    }
+    assert_eq!(
+        solution.moves,
+        vec![Move::Forward, Move::Forward, Move::Back, Move::Back]
+    );
+}
+
+#[test]
+fn move_conversions() {
+    let facing_east = IVec3::X;
+    assert_eq!(Move::Forward.to_absolute(facing_east), Move::East);
+    assert_eq!(Move::Back.to_absolute(facing_east), Move::West);
+    assert_eq!(Move::Left.to_absolute(facing_east), Move::North);
+    assert_eq!(Move::Right.to_absolute(facing_east), Move::South);
+    for m in Move::ABSOLUTE {
+        for player_dir in [IVec3::X, IVec3::Y, IVec3::NEG_X, IVec3::NEG_Y] {
+            assert_eq!(m.to_relative(player_dir).to_absolute(player_dir), m);
+        }
+    }
+    assert_eq!(Move::from_direction(IVec3::Z), None);
+}
+
+#[test]
+fn graph_serialization_round_trip() {
+    let description: LevelDescription = serde_json::from_str(SIMPLEST_POSSIBLE_LEVEL).unwrap();
+    let graph = generate_graph(&description);
+    let serialized = serde_json::to_string(&graph).unwrap();
+    let parsed: LevelGraph = serde_json::from_str(&serialized).unwrap();
+
+    assert_eq!(parsed.state_count(), graph.state_count());
+    for id in 0..graph.state_count() as StateId {
+        assert!(parsed.state(id) == graph.state(id));
+    }
+    assert!(parsed.edges().eq(graph.edges()));
+    assert_eq!(parsed.level_description(), graph.level_description());
+
+    // states that do not fit the level are rejected rather than panicking
+    let serialized: serde_json::Value = serde_json::to_value(&graph).unwrap();
+    for (field, value) in [
+        ("sausages", serde_json::json!([])),
+        ("player_pos", serde_json::json!([1000, 0, 1])),
+        ("player_dir", serde_json::json!([0, 0, 1])),
+    ] {
+        let mut broken = serialized.clone();
+        broken["states"][0][field] = value;
+        assert!(serde_json::from_value::<LevelGraph>(broken).is_err());
+    }
+}
+
+#[test]
+fn state_ids_are_stable() {
+    let description: LevelDescription = serde_json::from_str(SIMPLEST_POSSIBLE_LEVEL).unwrap();
+    let graph = generate_graph(&description);
+    let regenerated = generate_graph(&description);
+    for id in 0..graph.state_count() as StateId {
+        let state = graph.state(id).unwrap();
+        assert!(regenerated.state(id) == Some(state.clone()));
+        assert_eq!(graph.state_id(&state), Some(id));
+    }
+    assert_eq!(graph.initial_state().fingerprint(), 0xe725_f3de_e3db_2010);
+}
+
+#[test]
+fn packed_states() {
+    let graph = generate_graph(&serde_json::from_str(HAPPY_POOL).unwrap());
+    for id in 0..graph.state_count() as StateId {
+        assert_eq!(graph.state_id(&graph.state(id).unwrap()), Some(id));
+    }
+    assert!(graph.heap_size() / graph.state_count() < 100);
+
+    // sausages pushed off raised terrain fall all the way down to z=0
+    let description = ascii::parse_level(
+        "\
+z=2 x=0 y=0
+### ### ###
+z=3 x=0 y=0
+@>  ... (00 )00
+",
+    )
+    .unwrap();
+    assert_eq!(description.validate(), vec![]);
+    let graph = generate_graph(&description);
+    assert!((0..graph.state_count() as StateId)
+        .any(|id| graph.state(id).unwrap().sausages[0].pos.z == 0));
+}
+
+#[test]
+fn search_budget() {
+    let description: LevelDescription = serde_json::from_str(HAPPY_POOL).unwrap();
+    let full = generate_graph(&description);
+    assert_eq!(full.incomplete(), None);
+
+    let budget = SearchBudget {
+        max_states: Some(100),
+        ..Default::default()
+    };
+    let graph = generate_graph_with(&description, &budget, |_| ControlFlow::Continue(()));
+    assert_eq!(graph.incomplete(), Some(Incomplete::StateLimit));
+    assert!(graph.state_count() >= 100 && graph.state_count() < 104);
+    assert!(matches!(
+        graph.shortest_solution(),
+        ShortestSolution::Inconclusive
+    ));
+
+    let budget = SearchBudget {
+        max_depth: Some(2),
+        ..Default::default()
+    };
+    let graph = generate_graph_with(&description, &budget, |_| ControlFlow::Continue(()));
+    assert_eq!(graph.incomplete(), Some(Incomplete::DepthLimit));
+    assert!(graph.state_count() <= 1 + 4 + 16);
+
+    let mut calls = 0;
+    let graph = generate_graph_with(&description, &SearchBudget::default(), |progress| {
+        calls += 1;
+        assert!(progress.states <= full.state_count());
+        ControlFlow::Break(())
+    });
+    assert_eq!(graph.incomplete(), Some(Incomplete::Cancelled));
+    assert!(graph.state_count() < full.state_count());
+    assert_eq!(calls, 1);
+}
+
+#[test]
+fn incremental_expansion() {
+    let description: LevelDescription = serde_json::from_str(HAPPY_POOL).unwrap();
+    let full = generate_graph(&description);
+
+    let mut graph = LevelGraph::new(&description);
+    assert_eq!(graph.state_count(), 1);
+    assert_eq!(graph.incomplete(), Some(Incomplete::Unexplored));
+    assert_eq!(graph.successors(0), None);
+
+    let targets = graph.expand(0).unwrap();
+    assert_eq!(graph.successors(0), Some(targets));
+    for (m, target) in Move::RELATIVE.into_iter().zip(targets) {
+        assert_eq!(
+            graph.state(target).unwrap(),
+            graph.initial_state().get_next_state(m)
+        );
+    }
+
+    let added = graph.expand_from(targets[0], 2);
+    assert!(added > 0);
+    assert!(graph.expand_layers(1) > 0);
+    assert_eq!(graph.incomplete(), Some(Incomplete::DepthLimit));
+
+    while graph.incomplete().is_some() {
+        graph.expand_layers(5);
+    }
+    assert_eq!(graph.state_count(), full.state_count());
+    assert_eq!(graph.edges().count(), full.edges().count());
+    assert!(matches!(
+        graph.shortest_solution(),
+        ShortestSolution::Solved(_)
+    ));
+
+    // expanding one state at a time completes the graph just the same
+    let mut graph = LevelGraph::new(&description);
+    let mut id = 0;
+    while graph.incomplete().is_some() {
+        graph.expand(id);
+        id += 1;
+    }
+    assert_eq!(graph.state_count(), full.state_count());
+    assert_eq!(graph.edges().count(), full.edges().count());
+}
+
+#[test]
+fn astar() {
+    for preset in [SIMPLEST_POSSIBLE_LEVEL, HAPPY_POOL] {
+        let description: LevelDescription = serde_json::from_str(preset).unwrap();
+        let graph = generate_graph(&description);
+        let ShortestSolution::Solved(expected) = graph.shortest_solution() else {
+            panic!("preset should be solvable");
+        };
+
+        let (result, progress) = astar_solution(&description, &SearchBudget::default());
+        let ShortestSolution::Solved(solution) = result else {
+            panic!("A* did not find a solution");
+        };
+        assert_eq!(solution.moves.len(), expected.moves.len());
+        let mut state = LevelState::from(&description);
+        for (m, expected) in solution.moves.iter().zip(&solution.states[1..]) {
+            state = state.get_next_state(*m);
+            assert_eq!(&state, expected);
+        }
+        assert_eq!(state.get_status(), LevelStatus::Solution);
+        assert!(progress.states < graph.state_count());
+
+        for (state, moves_left) in solution.states.iter().zip((0..=solution.moves.len()).rev()) {
+            assert!(heuristic(state) as usize <= moves_left);
+        }
+    }
+
+    // swinging a lifted sausage carries its far half four tiles in one move
+    let description = ascii::parse_level(
+        "\
+z=0 x=0 y=-1
+### ### ### ###
+### ### ### ###
+### ### ### ###
+### ### ### ###
+z=1 x=0 y=-1
+... === ... ...
+... === ... ...
+### ### (01 )01
+... ### ... ...
+z=2 x=0 y=1
+@>
+",
+    )
+    .unwrap()
+    .with_rules(Ruleset {
+        return_to_start: false,
+        ..Ruleset::default()
+    });
+    let graph = generate_graph(&description);
+    let annotations = graph.annotations();
+    let states = (0..graph.state_count() as StateId).map(|id| graph.state(id).unwrap());
+    assert!(states.clone().any(|state| state.carried.is_some()));
+    for (state, annotation) in states.zip(&annotations) {
+        if let Some(distance) = annotation.distance {
+            assert!(heuristic(&state) <= distance);
+        }
+    }
+    let (ShortestSolution::Solved(expected), (ShortestSolution::Solved(solution), _)) = (
+        graph.shortest_solution(),
+        astar_solution(&description, &SearchBudget::default()),
+    ) else {
+        panic!("level should be solvable");
+    };
+    assert_eq!(solution.moves.len(), expected.moves.len());
+
+    let mut description: LevelDescription = serde_json::from_str(HAPPY_POOL).unwrap();
+    description.grills.clear();
+    let (result, progress) = astar_solution(&description, &SearchBudget::default());
+    assert!(matches!(result, ShortestSolution::Unsolvable));
+    assert_eq!(progress.explored, 0);
+}
+
+#[test]
+fn state_annotations() {
+    let description: LevelDescription = serde_json::from_str(HAPPY_POOL).unwrap();
+    let graph = generate_graph(&description);
+    let annotations = graph.annotations();
+    let ShortestSolution::Solved(solution) = graph.shortest_solution() else {
+        panic!("preset should be solvable");
+    };
+    assert_eq!(annotations[0].distance, Some(solution.moves.len() as u32));
+
+    for (id, annotation) in annotations.iter().enumerate() {
+        let state = graph.state(id as StateId).unwrap();
+        match state.get_status() {
+            LevelStatus::Solution => assert_eq!(annotation.distance, Some(0)),
+            LevelStatus::Lost | LevelStatus::Burnt | LevelStatus::Drowned => {
+                assert!(annotation.doomed)
+            }
+            LevelStatus::Unsolved => {
+                let targets = graph.successors(id as StateId).unwrap();
+                let closest = targets
+                    .iter()
+                    .filter_map(|target| annotations[*target as usize].distance)
+                    .min();
+                assert_eq!(annotation.distance, closest.map(|d| d + 1));
+                assert_eq!(
+                    annotation.doomed,
+                    targets.iter().all(|t| annotations[*t as usize].doomed)
+                );
+            }
+        }
+        assert_eq!(annotation.doomed, annotation.distance.is_none());
+    }
+    assert!(annotations.iter().enumerate().any(|(id, a)| a.doomed
+        && graph.state(id as StateId).unwrap().get_status() == LevelStatus::Unsolved));
+
+    let budget = SearchBudget {
+        max_depth: Some(3),
+        ..Default::default()
+    };
+    let graph = generate_graph_with(&description, &budget, |_| ControlFlow::Continue(()));
+    for (id, annotation) in graph.annotations().iter().enumerate() {
+        let status = graph.state(id as StateId).unwrap().get_status();
+        if status == LevelStatus::Unsolved && graph.successors(id as StateId).is_none() {
+            assert!(!annotation.doomed);
+        }
+    }
+    assert!(!graph.annotations()[0].doomed);
+}
+
+#[test]
+fn hints() {
+    let description: LevelDescription = serde_json::from_str(HAPPY_POOL).unwrap();
+    let graph = generate_graph(&description);
+    let annotations = graph.annotations();
+    let ShortestSolution::Solved(solution) = graph.shortest_solution() else {
+        panic!("preset should be solvable");
+    };
+
+    let mut state = LevelState::from(&description);
+    for _ in 0..solution.moves.len() {
+        let Hint::Move(m) = hint(&state, Some(&graph)) else {
+            panic!("expected a move");
+        };
+        let next_state = state.get_next_state(m);
+        let distance =
+            |state: &LevelState| annotations[graph.state_id(state).unwrap() as usize].distance;
+        assert_eq!(distance(&next_state), distance(&state).map(|d| d - 1));
+        state = next_state;
+    }
+    assert_eq!(hint(&state, Some(&graph)), Hint::Solved);
+
+    // without the graph the hints have to be searched for
+    let mut state = solution.states[solution.moves.len() / 2].clone();
+    let mut moves = 0;
+    while let Hint::Move(m) = hint(&state, None) {
+        state = state.get_next_state(m);
+        moves += 1;
+    }
+    assert_eq!(hint(&state, None), Hint::Solved);
+    assert_eq!(moves, solution.moves.len() - solution.moves.len() / 2);
+
+    let (doomed, lost) = (0..graph.state_count() as StateId)
+        .map(|id| (annotations[id as usize], graph.state(id).unwrap()))
+        .fold(
+            (None, None),
+            |(doomed, lost), (annotation, state)| match state.get_status() {
+                LevelStatus::Lost => (doomed, lost.or(Some(state))),
+                LevelStatus::Unsolved if annotation.doomed => (doomed.or(Some(state)), lost),
+                _ => (doomed, lost),
+            },
+        );
+    let (doomed, lost) = (doomed.unwrap(), lost.unwrap());
+    for graph in [Some(&graph), None] {
+        assert_eq!(
+            hint(&doomed, graph),
+            Hint::Unwinnable(LevelStatus::Unsolved)
+        );
+        assert_eq!(hint(&lost, graph), Hint::Unwinnable(LevelStatus::Lost));
+    }
+
+    // the annotations a graph keeps for its hints follow it as it grows
+    let mut graph = LevelGraph::new(&description);
+    let initial = graph.initial_state();
+    assert_eq!(graph.hint(&initial), None);
+    graph.explore(&SearchBudget::default(), |_| ControlFlow::Continue(()));
+    assert_eq!(graph.hint(&initial), Some(Hint::Move(solution.moves[0])));
+}
+
+#[test]
+fn level_validation() {
+    for preset in [SIMPLEST_POSSIBLE_LEVEL, HAPPY_POOL] {
+        let description: LevelDescription = serde_json::from_str(preset).unwrap();
+        assert_eq!(description.validate(), vec![]);
+    }
+
+    let description: LevelDescription = serde_json::from_str(
+        r#"{"start_pos":[4,1,1],"start_dir":[1,1,0],"ground":[[2,2,0],[3,2,0],[3,2,1],[4,2,0]],"grills":[[4,2,0],[5,1,0]],"sausages":[{"pos":[2,2,1],"cooked":[[0,0],[0,0]],"orientation":"Horizontal"},{"pos":[3,1,1],"cooked":[[0,0],[0,0]],"orientation":"Vertical"},{"pos":[3,1,1],"cooked":[[0,0],[0,0]],"orientation":"Horizontal"}]}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        description.validate(),
+        vec![
+            LevelProblem::GroundAndGrill {
+                tile: IVec3::new(4, 2, 0)
+            },
+            LevelProblem::InvalidStartDir {
+                dir: IVec3::new(1, 1, 0)
+            },
+            LevelProblem::PlayerNotOnGround {
+                tile: IVec3::new(4, 1, 0)
+            },
+            LevelProblem::SausageInTerrain {
+                tile: IVec3::new(3, 2, 1)
+            },
+            LevelProblem::SausageInTerrain {
+                tile: IVec3::new(3, 2, 1)
+            },
+            LevelProblem::OverlappingSausages {
+                tile: IVec3::new(3, 2, 1)
+            },
+            LevelProblem::OverlappingSausages {
+                tile: IVec3::new(3, 1, 1)
+            },
+            LevelProblem::PlayerInSausage {
+                tile: IVec3::new(4, 1, 1)
+            },
+        ]
+    );
+
+    // standing on a ladder is fine, a ladder inside the ground is not
+    let mut description = ascii::parse_level(
+        "\
+z=0 x=0 y=0
+HHH ###
+z=1 x=0 y=0
+@>
+",
+    )
+    .unwrap();
+    assert_eq!(description.validate(), vec![]);
+    description.ladders.insert(IVec3::new(1, 0, 0));
+    assert_eq!(
+        description.validate(),
+        vec![LevelProblem::LadderInTerrain {
+            tile: IVec3::new(1, 0, 0)
+        }]
+    );
+}
+
+#[test]
+fn ascii_levels() {
+    let text = "\
+z=0 x=2 y=1
+... ... ### === ===
+### ### ### === ===
+z=1 x=2 y=1
+... ... n00 ... ...
+@>  ... u00 ... ...
+";
+    let simplest: LevelDescription = serde_json::from_str(SIMPLEST_POSSIBLE_LEVEL).unwrap();
+    assert_eq!(ascii::parse_level(text), Ok(simplest.clone()));
+    assert_eq!(ascii::print_level(&simplest), text);
+
+    // rules other than the default ones are listed before the first layer
+    let ruled = simplest.with_rules(Ruleset {
+        return_to_start: false,
+        max_cooked: 2,
+        ..Ruleset::default()
+    });
+    let ruled_text = format!("rules return_to_start=false max_cooked=2\n{text}");
+    assert_eq!(ascii::parse_level(&ruled_text), Ok(ruled.clone()));
+    assert_eq!(ascii::print_level(&ruled), ruled_text);
+
+    // cook counts above 9 still take up a single character
+    let mut cooked = ruled.with_rules(Ruleset {
+        max_cooked: 20,
+        ..Ruleset::default()
+    });
+    cooked.sausages[0].cooked = [[12, 0], [0, 35]];
+    let cooked_text = ascii::print_level(&cooked);
+    assert!(cooked_text.contains("nc0") && cooked_text.contains("u0z"));
+    assert_eq!(ascii::parse_level(&cooked_text), Ok(cooked.clone()));
+    cooked.sausages[0].cooked[0][0] = 36;
+    assert!(ascii::parse_level(&ascii::print_level(&cooked)).is_err());
+
+    let state = LevelState::from(&simplest)
+        .get_next_state(Move::Forward)
+        .get_next_state(Move::Forward);
+    assert_eq!(
+        ascii::print_state(&state),
+        "\
+z=0 x=2 y=1
+... ... ### === ===
+### ### ### === ===
+z=1 x=2 y=1
+... ... ... ... n11
+... ... @>  ... u11
+"
+    );
+
+    let happy_pool: LevelDescription = serde_json::from_str(HAPPY_POOL).unwrap();
+    assert_eq!(
+        ascii::parse_level(&ascii::print_level(&happy_pool)),
+        Ok(happy_pool)
+    );
+
+    assert_eq!(
+        ascii::parse_level("z=0 x=0 y=0\n### ###\nz=1 x=0 y=0\n@>  (00\n")
+            .unwrap_err()
+            .line,
+        4
+    );
+    assert_eq!(ascii::parse_level("### ###\n").unwrap_err().line, 1);
+    assert_eq!(
+        ascii::parse_level("z=0 x=0 y=0\n###\nrules max_cooked=lots\n")
+            .unwrap_err()
+            .line,
+        3
+    );
+}
+
+#[test]
+fn rulesets() {
+    let description = serde_json::from_str::<LevelDescription>(SIMPLEST_POSSIBLE_LEVEL)
+        .unwrap()
+        .with_rules(Ruleset {
+            return_to_start: false,
+            ..Ruleset::default()
+        });
+    let ShortestSolution::Solved(solution) = generate_graph(&description).shortest_solution()
+    else {
+        panic!("level should be solvable");
+    };
+    assert_eq!(solution.moves, vec![Move::Forward, Move::Forward]);
+    let (result, _) = astar_solution(&description, &SearchBudget::default());
+    assert!(matches!(result, ShortestSolution::Solved(s) if s.moves.len() == 2));
+
+    // levels without rules, or with null ones from the front-end, use the
+    // default rules
+    let mut level: serde_json::Value = serde_json::from_str(SIMPLEST_POSSIBLE_LEVEL).unwrap();
+    level["rules"] = serde_json::Value::Null;
+    let description: LevelDescription = serde_json::from_value(level).unwrap();
+    assert_eq!(description.rules(), Ruleset::default());
+
+    // a face cooked twice is only burnt by the default rules, and the packed
+    // states keep counts up to the first burnt one
+    let mut description = ascii::parse_level(
+        "\
+z=0 x=0 y=0
+### ###
+z=1 x=0 y=0
+@>  n21
+... u13
+",
+    )
+    .unwrap();
+    let state = LevelState::from(&description);
+    assert_eq!(state.get_status(), LevelStatus::Burnt);
+    let mut states = packed::StateStore::new(packed::StateLayout::new(&description));
+    let (id, _) = states.insert(&state);
+    assert_eq!(
+        states.get(id, &state.description).sausages[0].cooked,
+        [[2, 1], [1, 2]]
+    );
+    description.rules.max_cooked = 3;
+    let state = LevelState::from(&description);
+    assert_eq!(state.get_status(), LevelStatus::Solution);
+    let mut states = packed::StateStore::new(packed::StateLayout::new(&description));
+    let (id, _) = states.insert(&state);
+    assert!(states.get(id, &state.description) == state);
+
+    let mut description = ascii::parse_level(
+        "\
+z=0 x=0 y=0
+### === ###
+z=1 x=0 y=0
+@>  ... ...
+",
+    )
+    .unwrap();
+    description.rules.grill_bounce = false;
+    let state = LevelState::from(&description).get_next_state(Move::Forward);
+    assert_eq!(state.player_pos, IVec3::new(1, 0, 1));
+
+    let mut description = ascii::parse_level(
+        "\
+z=0 x=0 y=0
+### ### ###
+z=1 x=0 y=0
+@>  ... (00 )00
+",
+    )
+    .unwrap();
+    let state = LevelState::from(&description);
+    assert_eq!(
+        state.get_next_state(Move::Forward).get_status(),
+        LevelStatus::Lost
+    );
+    description.rules.sausages_fall = false;
+    let state = LevelState::from(&description);
+    assert!(state.get_next_state(Move::Forward) == state);
+}
+
+/// Pushes `tile` of an ASCII level towards east on its own, without the
+/// player moving or anything falling, and draws what came of it.
+fn push_east(level: &str, tile: IVec3) -> Option<(Vec<push::Moved>, String)> {
+    let mut state = LevelState::from(&ascii::parse_level(level).unwrap());
+    let push = push::Push::resolve(&state, tile, IVec3::X)?;
+    push.apply(&mut state.sausages);
+    Some((push.moved, ascii::print_state(&state)))
+}
+
+#[test]
+fn push_resolution() {
+    use push::{Cause, Moved};
+    let draw =
+        |level: &str| ascii::print_state(&LevelState::from(&ascii::parse_level(level).unwrap()));
+    let moved = |sausage, rolls, cause| Moved {
+        sausage,
+        rolls,
+        cause,
+    };
+
+    let level = "\
+z=1 x=0 y=0
+@>  (00 )00 ###
+";
+    assert_eq!(push_east(level, IVec3::new(1, 0, 1)), None);
+    assert_eq!(push_east(level, IVec3::new(3, 0, 1)), None);
+    assert_eq!(
+        push_east(level, IVec3::new(0, 1, 1)),
+        Some((vec![], draw(level)))
+    );
+
+    // both halves run into the same sausage, which only moves once
+    let level = "\
+z=1 x=0 y=0
+@>  n00 n10
+... u00 u10
+";
+    assert_eq!(
+        push_east(level, IVec3::new(1, 1, 1)),
+        Some((
+            vec![
+                moved(0, true, Cause::Start),
+                moved(1, true, Cause::Pushed(0))
+            ],
+            draw(
+                "\
+z=1 x=0 y=0
+@>  ... n00 n01
+... ... u00 u01
+"
+            )
+        ))
+    );
+
+    // a sliding sausage rolls the one across its path, a sausage resting on
+    // both moves once and without rolling
+    let level = "\
+z=1 x=0 y=0
+@>  (00 )00 n10
+... ... ... u10
+z=2 x=0 y=0
+... ... (10 )10
+";
+    assert_eq!(
+        push_east(level, IVec3::new(1, 0, 1)),
+        Some((
+            vec![
+                moved(0, false, Cause::Start),
+                moved(1, true, Cause::Pushed(0)),
+                moved(2, false, Cause::Carried(0))
+            ],
+            draw(
+                "\
+z=1 x=0 y=0
+@>  ... (00 )00 n01
+... ... ... ... u01
+z=2 x=0 y=0
+... ... ... (10 )10
+"
+            )
+        ))
+    );
+
+    // a sausage on top that has no room stays behind
+    let level = "\
+z=1 x=0 y=0
+@>  (00 )00 ...
+z=2 x=0 y=0
+... n00 ... ...
+... u00 ### ...
+";
+    assert_eq!(
+        push_east(level, IVec3::new(1, 0, 1)),
+        Some((
+            vec![moved(0, false, Cause::Start)],
+            draw(
+                "\
+z=1 x=0 y=0
+@>  ... (00 )00
+z=2 x=0 y=0
+... n00 ... ...
+... u00 ### ...
+"
+            )
+        ))
+    );
+}
+
+/// A randomly generated level and the moves played in it.
+#[derive(Debug, Clone)]
+struct Scenario {
+    description: LevelDescription,
+    moves: Vec<Move>,
+}
+
+/// Checks a single move, given the state before and after it.
+type Invariant = dyn Fn(&LevelState, Move, &LevelState) -> Result<(), String>;
+
+/// A small level with one or two sausages, played by random rules.
+fn random_level(rng: &mut impl rand::Rng) -> LevelDescription {
+    loop {
+        let size = IVec3::new(rng.gen_range(3..7), rng.gen_range(3..7), 1);
+        let mut ground = HashSet::new();
+        let mut grills = HashSet::new();
+        let mut ladders = HashSet::new();
+        for x in 0..size.x {
+            for y in 0..size.y {
+                let (bottom, top) = (IVec3::new(x, y, 0), IVec3::new(x, y, 1));
+                match rng.gen_range(0..12) {
+                    0..=4 => ground.insert(bottom),
+                    5..=6 => grills.insert(bottom),
+                    7 => ground.insert(bottom) && ground.insert(top),
+                    8 => ground.insert(bottom) && grills.insert(top),
+                    9 => ground.insert(bottom) && ladders.insert(top),
+                    _ => false,
+                };
+            }
+        }
+        // the player starts on top of a column of ground or ladders
+        let mut tiles: Vec<IVec3> = ground
+            .union(&ladders)
+            .filter(|tile| !ground.contains(&(**tile + IVec3::Z)))
+            .filter(|tile| !grills.contains(&(**tile + IVec3::Z)))
+            .filter(|tile| !ladders.contains(&(**tile + IVec3::Z)))
+            .copied()
+            .collect();
+        tiles.sort_by_key(|tile| tile.to_array());
+        if tiles.is_empty() {
+            continue;
         }
+        let sausages = (0..rng.gen_range(1..3))
+            .map(|_| Sausage {
+                pos: IVec3::new(
+                    rng.gen_range(0..size.x),
+                    rng.gen_range(0..size.y),
+                    rng.gen_range(1..3),
+                ),
+                cooked: [[0; 2]; 2],
+                orientation: match rng.gen_bool(0.5) {
+                    true => SausageOrientation::Horizontal,
+                    false => SausageOrientation::Vertical,
+                },
+            })
+            .collect();
+        let description = LevelDescription {
+            start_pos: tiles[rng.gen_range(0..tiles.len())] + IVec3::Z,
+            start_dir: Move::ABSOLUTE[rng.gen_range(0..4)].direction(IVec3::X),
+            ground,
+            grills,
+            ladders,
+            sausages,
+            rules: Ruleset {
+                return_to_start: rng.gen_bool(0.8),
+                max_cooked: rng.gen_range(1..4),
+                grill_bounce: rng.gen_bool(0.8),
+                sausages_fall: rng.gen_bool(0.8),
+            },
+        };
+        if description.validate().is_empty() {
+            return description;
+        }
+    }
+}
+
+fn engine_invariants(before: &LevelState, m: Move, after: &LevelState) -> Result<(), String> {
+    if before.get_next_state(m) != *after {
+        return Err("the same move gave different states".to_string());
+    }
+    let floats = |state: &LevelState, i: usize| {
+        let sausage = &state.sausages[i];
+        state.carried != Some(i)
+            && sausage.pos.z >= 1
+            && ![sausage.pos, sausage.pos2()].into_iter().any(|half| {
+                state.supports(half + IVec3::NEG_Z) || half + IVec3::NEG_Z == state.player_pos
+            })
+    };
+
+    // in a level where nothing is falling, moves into terrain change nothing
+    // and a turn with nothing in the way of the fork always turns
+    let tile_type = |tile: IVec3| before.description.get_tile_type(tile);
+    let (pos, dir) = (before.player_pos, before.player_dir);
+    let side = m.direction(dir);
+    let settled = (0..before.sausages.len()).all(|i| !floats(before, i));
+    match m.to_relative(dir) {
+        _ if !settled => (),
+        Move::Forward
+            if matches!(tile_type(pos + dir), TileType::Ground | TileType::Grill)
+                && after != before =>
+        {
+            return Err("walking into terrain changed the state".to_string());
+        }
+        Move::Back if tile_type(pos - dir) != TileType::Water && after != before => {
+            return Err("backing into terrain changed the state".to_string());
+        }
+        Move::Left | Move::Right
+            if before.held().is_none()
+                && before.description.rules.sausages_fall
+                && [pos + dir + side, pos + side].into_iter().all(|tile| {
+                    tile_type(tile) == TileType::Water && before.get_sausage(tile).is_none()
+                })
+                && after.player_dir != side =>
+        {
+            return Err("turning with nothing in the way of the fork did not turn".to_string());
+        }
+        _ => (),
+    }
+    if before.sausages.len() != after.sausages.len() {
+        return Err("the number of sausages changed".to_string());
+    }
+
+    let mut occupied = HashSet::new();
+    for sausage in &after.sausages {
+        for tile in [sausage.pos, sausage.pos2()] {
+            if !occupied.insert(tile) {
+                return Err(format!("two sausages overlap at {tile}"));
+            }
+        }
+    }
+    if occupied.contains(&after.player_pos) {
+        return Err(format!(
+            "the player is inside a sausage at {}",
+            after.player_pos
+        ));
+    }
+    if after.description.get_tile_type(after.player_pos) != TileType::Water {
+        return Err(format!(
+            "the player is inside the terrain at {}",
+            after.player_pos
+        ));
+    }
+    if after.player_pos.z > 1 && !after.supports(after.player_pos + IVec3::NEG_Z) {
+        return Err(format!("the player floats at {}", after.player_pos));
+    }
+    for i in 0..after.sausages.len() {
+        // without falling a sausage placed in the air stays there
+        if floats(after, i) && (after.description.rules.sausages_fall || !floats(before, i)) {
+            return Err(format!("a sausage floats at {}", after.sausages[i].pos));
+        }
+    }
+    if after.carried.is_some() && after.carried != after.held() {
+        return Err("the carried sausage is not on the fork".to_string());
+    }
+    let in_terrain = |state: &LevelState| {
+        matches!(
+            tile_type(state.player_pos + state.player_dir),
+            TileType::Ground | TileType::Grill
+        )
+    };
+    if in_terrain(after) && !in_terrain(before) {
+        return Err(format!(
+            "the fork is in the terrain at {}",
+            after.player_pos + after.player_dir
+        ));
+    }
+
+    for (old, new) in before.sausages.iter().zip(&after.sausages) {
+        let mut old = old.cooked.as_flattened().to_vec();
+        let mut new = new.cooked.as_flattened().to_vec();
+        old.sort();
+        new.sort();
+        if old.iter().zip(&new).any(|(old, new)| new < old) {
+            return Err("a sausage became less cooked".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Plays the scenario until the level is over, returning the index of the
+/// first move that broke the invariant and why.
+fn find_violation(scenario: &Scenario, invariant: &Invariant) -> Option<(usize, String)> {
+    let mut state = LevelState::from(&scenario.description);
+    for (i, m) in scenario.moves.iter().enumerate() {
+        if state.get_status() != LevelStatus::Unsolved {
+            break;
+        }
+        let next_state = state.get_next_state(*m);
+        if let Err(e) = invariant(&state, *m, &next_state) {
+            return Some((i, e));
+        }
+        state = next_state;
+    }
+    None
+}
+
+/// Makes a failing scenario as small as possible while it keeps failing,
+/// dropping moves, tiles and sausages one at a time, or starting the level
+/// after its first move.
+fn shrink(mut scenario: Scenario, invariant: &Invariant) -> Scenario {
+    let fails = |scenario: &Scenario| {
+        scenario.description.validate().is_empty() && find_violation(scenario, invariant).is_some()
+    };
+    if let Some((i, _)) = find_violation(&scenario, invariant) {
+        scenario.moves.truncate(i + 1);
+    }
+
+    loop {
+        let mut candidates = Vec::new();
+        if let Some(first) = scenario.moves.first() {
+            // start the level from where the first move leads instead
+            let state = LevelState::from(&scenario.description).get_next_state(*first);
+            let mut candidate = scenario.clone();
+            candidate.description.start_pos = state.player_pos;
+            candidate.description.start_dir = state.player_dir;
+            candidate.description.sausages = state.sausages;
+            candidate.moves.remove(0);
+            candidates.push(candidate);
+        }
+        for i in 0..scenario.moves.len() {
+            let mut candidate = scenario.clone();
+            candidate.moves.remove(i);
+            candidates.push(candidate);
+        }
+        for i in 0..scenario.description.sausages.len() {
+            let mut candidate = scenario.clone();
+            candidate.description.sausages.remove(i);
+            candidates.push(candidate);
+        }
+        let mut tiles: Vec<IVec3> = scenario
+            .description
+            .ground
+            .iter()
+            .chain(&scenario.description.grills)
+            .chain(&scenario.description.ladders)
+            .copied()
+            .collect();
+        tiles.sort_by_key(|tile| tile.to_array());
+        for tile in tiles {
+            let mut candidate = scenario.clone();
+            candidate.description.ground.remove(&tile);
+            candidate.description.grills.remove(&tile);
+            candidate.description.ladders.remove(&tile);
+            candidates.push(candidate);
+        }
+
+        match candidates.into_iter().find(|candidate| fails(candidate)) {
+            Some(smaller) => scenario = smaller,
+            None => return scenario,
+        }
+    }
+}
+
+/// The scenario as an entry for `test-cases.json`, to be completed with what
+/// should have happened.
+fn scenario_to_test_case(scenario: &Scenario, title: &str) -> String {
+    let start: Vec<String> = ascii::print_level(&scenario.description)
+        .lines()
+        .map(str::to_string)
+        .collect();
+    let steps: Vec<serde_json::Value> = scenario
+        .moves
+        .iter()
+        .map(|m| serde_json::json!({ "move": m }))
+        .collect();
+    serde_json::to_string_pretty(&serde_json::json!({
+        "title": title,
+        "start": start,
+        "steps": steps,
+    }))
+    .unwrap()
+}
+
+/// Plays random moves in random levels. If `invariant` is broken the error
+/// says how, followed by the shrunk scenario as a test case.
+fn check_random_scenarios(invariant: &Invariant) -> Result<(), String> {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    for seed in 0..300 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let description = random_level(&mut rng);
+        let moves = (0..40)
+            .map(|_| match rng.gen_bool(0.5) {
+                true => Move::RELATIVE[rng.gen_range(0..4)],
+                false => Move::ABSOLUTE[rng.gen_range(0..4)],
+            })
+            .collect();
+        let scenario = Scenario { description, moves };
+        if find_violation(&scenario, invariant).is_some() {
+            let scenario = shrink(scenario, invariant);
+            let (_, e) = find_violation(&scenario, invariant).unwrap();
+            return Err(format!(
+                "seed {seed}: {e}\n{}",
+                scenario_to_test_case(&scenario, &format!("random seed {seed}"))
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn move_engine_invariants() {
+    if let Err(e) = check_random_scenarios(&engine_invariants) {
+        panic!("{e}");
+    }
+}
+
+#[test]
+fn shrinking() {
+    let invariant: &Invariant =
+        &|_, _, after| match after.sausages.iter().any(|s| s.cooked[0][0] > 0) {
+            false => Ok(()),
+            true => Err("cooked".to_string()),
+        };
+    let message = check_random_scenarios(invariant).unwrap_err();
+    let (_, test_case) = message.split_once('\n').unwrap();
+    let test_case: TestCase = serde_json::from_str(test_case).unwrap();
+
+    // cooking a sausage takes a single push onto a grill
+    assert_eq!(test_case.steps.len(), 1);
+    let TestLevel::Ascii(lines) = &test_case.start else {
+        panic!("expected an ASCII level");
     };
+    let description = ascii::parse_level(&lines.join("\n")).unwrap();
+    assert_eq!(description.sausages.len(), 1);
+    assert!(description.ground.len() + description.grills.len() <= 4);
 }